@@ -0,0 +1,71 @@
+//! Compiling and evaluating expressions shaped like the ones found in transaction templates.
+//!
+//! To compare against the tree-walking interpreter that preceded evaluation plans,
+//! save a baseline on a checkout of the 0.11.3 release with this file copied in:
+//!
+//!     cargo bench --bench evaluate -- --save-baseline tree-walk
+//!
+//! and run `cargo bench --bench evaluate -- --baseline tree-walk` on this revision.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sqlx_ledger_cel_interpreter::{CelArray, CelContext, CelExpression, CelMap, CelValue};
+
+const EXPRESSIONS: [(&str, &str); 6] = [
+    (
+        "constant_uuid",
+        "uuid('6b7a3f1e-4a3e-4c0e-9d4b-3e4f7a0b1c2d')",
+    ),
+    ("param_lookup", "params.sender"),
+    (
+        "fee",
+        "max(round(percent(params.amount, 35), 2), decimal('1.00'))",
+    ),
+    (
+        "condition",
+        "params.amount > decimal('100') ? SETTLED : PENDING",
+    ),
+    ("string_concat", "'payout-' + string(params.count)"),
+    ("comprehension", "sum(params.items.map(x, x * 2))"),
+];
+
+fn context() -> CelContext {
+    let mut ctx = CelContext::new();
+    for layer in ["SETTLED", "PENDING", "ENCUMBERED", "DEBIT", "CREDIT"] {
+        ctx.add_variable(layer, layer);
+    }
+    let mut params = CelMap::new();
+    params.insert("sender", uuid::Uuid::new_v4());
+    params.insert("amount", CelValue::Decimal("1250.75".parse().unwrap()));
+    params.insert("count", 42);
+    params.insert(
+        "items",
+        CelArray::from((0..10).map(CelValue::Int).collect::<Vec<_>>()),
+    );
+    ctx.add_variable("params", params);
+    ctx
+}
+
+fn evaluate(c: &mut Criterion) {
+    let ctx = context();
+    let mut group = c.benchmark_group("evaluate");
+    for (name, source) in EXPRESSIONS {
+        let expr: CelExpression = source.parse().unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&expr).evaluate(&ctx).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    for (name, source) in EXPRESSIONS {
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(source).parse::<CelExpression>().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, evaluate, compile);
+criterion_main!(benches);
@@ -0,0 +1,214 @@
+//! Evaluates CEL expressions the way transaction templates do, without a database.
+//!
+//! ```text
+//! sqlx-ledger-cel --params params.json "params.amount * 2"
+//! sqlx-ledger-cel --params params.json --var SETTLED='"SETTLED"'
+//! ```
+//!
+//! Without an expression an interactive session is started.
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+use sqlx_ledger_cel_interpreter::{
+    CelClock, CelContext, CelExpression, CelKey, CelMap, CelType, CelValue, Tz,
+};
+
+use std::{path::PathBuf, process::ExitCode};
+
+const USAGE: &str = "\
+Usage: sqlx-ledger-cel [OPTIONS] [EXPRESSION]
+
+Evaluates EXPRESSION, or starts an interactive session if it is omitted.
+
+Options:
+  -p, --params <FILE>     JSON object of template params, available as `params`
+  -v, --var <NAME=JSON>   Adds a variable, values that aren't valid JSON are strings
+  -t, --timezone <TZ>     Timezone of `date()`, ie. America/El_Salvador (default UTC)
+  -h, --help              Prints this message";
+
+const HISTORY_FILE: &str = ".sqlx_ledger_cel_history";
+
+#[derive(Default)]
+struct Options {
+    params: Option<PathBuf>,
+    vars: Vec<(String, String)>,
+    timezone: Option<Tz>,
+    expression: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let ctx = match context(&options) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match options.expression {
+        Some(source) => match evaluate(&ctx, &source) {
+            Ok(output) => {
+                println!("{output}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
+            }
+        },
+        None => match repl(&ctx) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+/// Returns `None` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-p" | "--params" => {
+                let file = args.next().ok_or("--params requires a file")?;
+                options.params = Some(PathBuf::from(file));
+            }
+            "-t" | "--timezone" => {
+                let timezone = args.next().ok_or("--timezone requires a timezone")?;
+                let timezone = timezone
+                    .parse()
+                    .map_err(|_| format!("Unknown timezone '{timezone}'"))?;
+                options.timezone = Some(timezone);
+            }
+            "-v" | "--var" => {
+                let var = args.next().ok_or("--var requires NAME=JSON")?;
+                let (name, value) = var
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid variable '{var}', expected NAME=JSON"))?;
+                options.vars.push((name.to_string(), value.to_string()));
+            }
+            // Negative numbers such as '-1.5' are expressions, '-x' or '--x' are options
+            _ if arg.starts_with('-')
+                && arg.trim_start_matches('-').starts_with(char::is_alphabetic) =>
+            {
+                return Err(format!("Unknown option '{arg}'"));
+            }
+            _ if options.expression.is_none() => options.expression = Some(arg),
+            _ => return Err(format!("Unexpected argument '{arg}'")),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn context(options: &Options) -> Result<CelContext, String> {
+    let mut ctx = CelContext::new();
+    if let Some(timezone) = options.timezone {
+        ctx.set_clock(CelClock::system().with_timezone(timezone));
+    }
+    if let Some(path) = options.params.as_ref() {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+        let params: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Couldn't parse {}: {e}", path.display()))?;
+        if !params.is_object() {
+            return Err(format!("{} must contain a JSON object", path.display()));
+        }
+        ctx.add_variable("params", CelValue::from(params));
+    }
+    for (name, value) in options.vars.iter() {
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.clone()));
+        ctx.add_variable(name.as_str(), CelValue::from(value));
+    }
+    Ok(ctx)
+}
+
+fn evaluate(ctx: &CelContext, source: &str) -> Result<String, String> {
+    let expression: CelExpression = source.parse().map_err(|e| format!("{e}"))?;
+    let value = expression.evaluate(ctx).map_err(|e| format!("{e}"))?;
+    Ok(format!("{} : {:?}", render(&value), CelType::from(&value)))
+}
+
+fn repl(ctx: &CelContext) -> Result<(), ReadlineError> {
+    let mut editor = DefaultEditor::new()?;
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(history) = history.as_ref() {
+        // There is no history on first use
+        let _ = editor.load_history(history);
+    }
+    println!("Enter an expression to evaluate it, :quit or Ctrl-D to exit.");
+    loop {
+        match editor.readline("cel> ") {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(line)?;
+                if line == ":quit" || line == ":q" {
+                    break;
+                }
+                match evaluate(ctx, line) {
+                    Ok(output) => println!("{output}"),
+                    Err(e) => println!("{e}"),
+                }
+            }
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    if let Some(history) = history.as_ref() {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+/// Renders a value as the CEL expression that produces it.
+fn render(value: &CelValue) -> String {
+    match value {
+        CelValue::Map(map) => render_map(map),
+        CelValue::List(list) => format!(
+            "[{}]",
+            list.iter().map(render).collect::<Vec<_>>().join(", ")
+        ),
+        CelValue::Int(i) => i.to_string(),
+        CelValue::UInt(u) => format!("{u}u"),
+        CelValue::Double(d) => format!("{d:?}"),
+        CelValue::String(s) => format!("'{}'", s.replace('\'', "\\'")),
+        CelValue::Bytes(b) => format!("b'{}'", b.escape_ascii()),
+        CelValue::Bool(b) => b.to_string(),
+        CelValue::Null => "null".to_string(),
+        CelValue::Decimal(d) => format!("{d}m"),
+        CelValue::Date(d) => format!("date('{d}')"),
+        CelValue::Uuid(u) => format!("uuid('{u}')"),
+    }
+}
+
+fn render_map(map: &CelMap) -> String {
+    let mut keys: Vec<&CelKey> = map.keys().collect();
+    keys.sort();
+    let entries = keys.into_iter().map(|key| {
+        let value = render(&map.get(key.clone()));
+        match (map.type_name(), key) {
+            (Some(_), CelKey::String(field)) => format!("{field}: {value}"),
+            _ => format!("{}: {value}", render(&CelValue::from(key))),
+        }
+    });
+    let entries = entries.collect::<Vec<_>>().join(", ");
+    match map.type_name() {
+        Some(name) => format!("{name}{{{entries}}}"),
+        None => format!("{{{entries}}}"),
+    }
+}
@@ -0,0 +1,130 @@
+use cel_parser::ast::{self, Expression};
+
+use std::cell::Cell;
+
+use crate::{error::*, value::*};
+
+/// Maximum nesting depth accepted when parsing a [CelExpression](crate::CelExpression).
+pub const MAX_NESTING_DEPTH: usize = 100;
+
+/// Limits on the resources a single evaluation may consume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CelBudget {
+    /// Maximum depth of nested expressions being evaluated at once.
+    pub max_depth: usize,
+    /// Maximum number of expression nodes evaluated, including repeated
+    /// evaluations of comprehension bodies.
+    pub max_node_visits: usize,
+    /// Maximum number of elements in any list or map produced.
+    pub max_list_size: usize,
+    /// Maximum length in bytes of any string or bytes value produced.
+    pub max_string_size: usize,
+}
+
+impl Default for CelBudget {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_node_visits: 100_000,
+            max_list_size: 10_000,
+            max_string_size: 100_000,
+        }
+    }
+}
+
+/// Tracks consumption of a [CelBudget] during one evaluation.
+pub(crate) struct Meter<'b> {
+    budget: &'b CelBudget,
+    depth: Cell<usize>,
+    visits: Cell<usize>,
+}
+
+/// Leaves the current nesting level when dropped.
+pub(crate) struct DepthGuard<'m, 'b>(&'m Meter<'b>);
+
+impl<'b> Meter<'b> {
+    pub(crate) fn new(budget: &'b CelBudget) -> Self {
+        Self {
+            budget,
+            depth: Cell::new(0),
+            visits: Cell::new(0),
+        }
+    }
+
+    pub(crate) fn enter(&self) -> Result<DepthGuard<'_, 'b>, CelError> {
+        let visits = self.visits.get() + 1;
+        if visits > self.budget.max_node_visits {
+            return Err(CelError::BudgetExceeded(format!(
+                "more than {} nodes evaluated",
+                self.budget.max_node_visits
+            )));
+        }
+        let depth = self.depth.get() + 1;
+        if depth > self.budget.max_depth {
+            return Err(CelError::BudgetExceeded(format!(
+                "evaluation nested deeper than {}",
+                self.budget.max_depth
+            )));
+        }
+        self.visits.set(visits);
+        self.depth.set(depth);
+        Ok(DepthGuard(self))
+    }
+
+    pub(crate) fn check_size(&self, val: &CelValue) -> Result<(), CelError> {
+        let (size, max, kind) = match val {
+            CelValue::List(list) => (list.len(), self.budget.max_list_size, "list"),
+            CelValue::Map(map) => (map.len(), self.budget.max_list_size, "map"),
+            CelValue::String(s) => (s.len(), self.budget.max_string_size, "string"),
+            CelValue::Bytes(b) => (b.len(), self.budget.max_string_size, "bytes"),
+            _ => return Ok(()),
+        };
+        if size > max {
+            return Err(CelError::BudgetExceeded(format!(
+                "{kind} of size {size} exceeds {max}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DepthGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.0.depth.set(self.0.depth.get() - 1);
+    }
+}
+
+/// Depth of the deepest node in `expr`, ignoring span wrappers.
+/// Computed iteratively so arbitrarily deep input can't overflow the stack.
+pub(crate) fn nesting_depth(expr: &Expression) -> usize {
+    use Expression::*;
+    let mut max = 0;
+    let mut stack = vec![(expr, 1)];
+    while let Some((expr, depth)) = stack.pop() {
+        max = max.max(depth);
+        let next = depth + 1;
+        match expr {
+            Spanned(_, inner) => stack.push((inner, depth)),
+            Ternary(cond, left, right) => {
+                stack.extend([(cond.as_ref(), next), (left, next), (right, next)])
+            }
+            Relation(_, left, right) | Arithmetic(_, left, right) => {
+                stack.extend([(left.as_ref(), next), (right, next)])
+            }
+            Unary(_, expr) => stack.push((expr, next)),
+            Member(target, member) => {
+                stack.push((target, next));
+                match member.as_ref() {
+                    ast::Member::Attribute(_) => (),
+                    ast::Member::FunctionCall(args) => stack.extend(args.iter().map(|e| (e, next))),
+                    ast::Member::Index(idx) => stack.push((idx, next)),
+                }
+            }
+            List(exprs) => stack.extend(exprs.iter().map(|e| (e, next))),
+            Map(entries) => stack.extend(entries.iter().flat_map(|(k, v)| [(k, next), (v, next)])),
+            Struct(_, fields) => stack.extend(fields.iter().map(|(_, v)| (v, next))),
+            Literal(_) | Ident(_) => (),
+        }
+    }
+    max
+}
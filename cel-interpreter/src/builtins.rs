@@ -1,13 +1,22 @@
-use chrono::{NaiveDate, Utc};
+use chrono::NaiveDate;
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
 
-use std::sync::Arc;
+use std::{cmp::Ordering, sync::Arc};
+
+use cel_parser::{ArithmeticOp, RelationOp};
 
 use super::value::*;
-use crate::error::*;
+use crate::{
+    cel_type::*,
+    clock::*,
+    error::*,
+    interpreter::{evaluate_arithmetic, evaluate_relation},
+};
 
-pub(crate) fn date(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+/// `date()` is the current date according to `clock`, `date('2023-01-31')` parses a date.
+pub(crate) fn date(clock: &CelClock, args: Vec<CelValue>) -> Result<CelValue, CelError> {
     if args.is_empty() {
-        return Ok(CelValue::Date(Utc::now().date_naive()));
+        return Ok(CelValue::Date(clock.today()));
     }
 
     let s: Arc<String> = assert_arg(args.first())?;
@@ -23,13 +32,333 @@ pub(crate) fn uuid(args: Vec<CelValue>) -> Result<CelValue, CelError> {
 }
 
 pub(crate) fn decimal(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::Int(i)) => Ok(CelValue::Decimal(Decimal::from(*i))),
+        Some(CelValue::UInt(u)) => Ok(CelValue::Decimal(Decimal::from(*u))),
+        Some(CelValue::Decimal(d)) => Ok(CelValue::Decimal(*d)),
+        arg => {
+            let s: Arc<String> = assert_arg(arg)?;
+            Ok(CelValue::Decimal(
+                s.parse()
+                    .map_err(|e| CelError::DecimalError(format!("{e:?}")))?,
+            ))
+        }
+    }
+}
+
+pub(crate) fn string(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let s = match args.first() {
+        Some(CelValue::String(s)) => return Ok(CelValue::String(Arc::clone(s))),
+        Some(CelValue::Int(i)) => i.to_string(),
+        Some(CelValue::UInt(u)) => u.to_string(),
+        Some(CelValue::Double(d)) => d.to_string(),
+        Some(CelValue::Bool(b)) => b.to_string(),
+        Some(CelValue::Decimal(d)) => d.to_string(),
+        Some(CelValue::Date(d)) => d.to_string(),
+        Some(CelValue::Uuid(u)) => u.to_string(),
+        Some(CelValue::Bytes(b)) => String::from_utf8(b.to_vec())
+            .map_err(|_| CelError::InvalidConversion("bytes are not valid UTF-8".to_string()))?,
+        Some(v) => return Err(conversion_overload("string", v)),
+        None => return Err(CelError::MissingArgument),
+    };
+    Ok(CelValue::String(Arc::new(s)))
+}
+
+pub(crate) fn int(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let out_of_range = |v: &dyn std::fmt::Display| {
+        CelError::InvalidConversion(format!("int({v}) is out of range"))
+    };
+    match args.first() {
+        Some(CelValue::Int(i)) => Ok(CelValue::Int(*i)),
+        Some(CelValue::UInt(u)) => i64::try_from(*u)
+            .map(CelValue::Int)
+            .map_err(|_| out_of_range(u)),
+        Some(CelValue::Double(d)) => {
+            let t = d.trunc();
+            if t.is_finite() && t >= i64::MIN as f64 && t < i64::MAX as f64 {
+                Ok(CelValue::Int(t as i64))
+            } else {
+                Err(out_of_range(d))
+            }
+        }
+        Some(CelValue::Decimal(d)) => d
+            .trunc()
+            .to_i64()
+            .map(CelValue::Int)
+            .ok_or_else(|| out_of_range(d)),
+        Some(CelValue::String(s)) => s
+            .parse()
+            .map(CelValue::Int)
+            .map_err(|_| CelError::InvalidConversion(format!("Cannot convert '{s}' to int"))),
+        Some(v) => Err(conversion_overload("int", v)),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+pub(crate) fn uint(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let out_of_range = |v: &dyn std::fmt::Display| {
+        CelError::InvalidConversion(format!("uint({v}) is out of range"))
+    };
+    match args.first() {
+        Some(CelValue::UInt(u)) => Ok(CelValue::UInt(*u)),
+        Some(CelValue::Int(i)) => u64::try_from(*i)
+            .map(CelValue::UInt)
+            .map_err(|_| out_of_range(i)),
+        Some(CelValue::Double(d)) => {
+            let t = d.trunc();
+            if t.is_finite() && t >= 0.0 && t < u64::MAX as f64 {
+                Ok(CelValue::UInt(t as u64))
+            } else {
+                Err(out_of_range(d))
+            }
+        }
+        Some(CelValue::Decimal(d)) => d
+            .trunc()
+            .to_u64()
+            .map(CelValue::UInt)
+            .ok_or_else(|| out_of_range(d)),
+        Some(CelValue::String(s)) => s
+            .parse()
+            .map(CelValue::UInt)
+            .map_err(|_| CelError::InvalidConversion(format!("Cannot convert '{s}' to uint"))),
+        Some(v) => Err(conversion_overload("uint", v)),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+pub(crate) fn double(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::Double(d)) => Ok(CelValue::Double(*d)),
+        Some(CelValue::Int(i)) => Ok(CelValue::Double(*i as f64)),
+        Some(CelValue::UInt(u)) => Ok(CelValue::Double(*u as f64)),
+        Some(CelValue::Decimal(d)) => d
+            .to_f64()
+            .map(CelValue::Double)
+            .ok_or_else(|| CelError::InvalidConversion(format!("Cannot convert {d} to double"))),
+        Some(CelValue::String(s)) => s
+            .parse()
+            .map(CelValue::Double)
+            .map_err(|_| CelError::InvalidConversion(format!("Cannot convert '{s}' to double"))),
+        Some(v) => Err(conversion_overload("double", v)),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+fn conversion_overload(name: &str, v: &CelValue) -> CelError {
+    CelError::NoMatchingOverload(format!("Cannot apply '{name}' to {:?}", CelType::from(v)))
+}
+
+pub(crate) fn starts_with(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let (s, prefix) = string_args(&args)?;
+    Ok(CelValue::Bool(s.starts_with(prefix.as_str())))
+}
+
+pub(crate) fn ends_with(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let (s, suffix) = string_args(&args)?;
+    Ok(CelValue::Bool(s.ends_with(suffix.as_str())))
+}
+
+pub(crate) fn contains(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let (s, substring) = string_args(&args)?;
+    Ok(CelValue::Bool(s.contains(substring.as_str())))
+}
+
+pub(crate) fn matches(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let (s, pattern) = string_args(&args)?;
+    let re = regex::Regex::new(&pattern)
+        .map_err(|e| CelError::InvalidRegex(format!("'{pattern}': {e}")))?;
+    Ok(CelValue::Bool(re.is_match(&s)))
+}
+
+pub(crate) fn lower_ascii(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let s: Arc<String> = assert_arg(args.first())?;
+    Ok(CelValue::String(Arc::new(s.to_ascii_lowercase())))
+}
+
+pub(crate) fn upper_ascii(args: Vec<CelValue>) -> Result<CelValue, CelError> {
     let s: Arc<String> = assert_arg(args.first())?;
+    Ok(CelValue::String(Arc::new(s.to_ascii_uppercase())))
+}
+
+fn string_args(args: &[CelValue]) -> Result<(Arc<String>, Arc<String>), CelError> {
+    Ok((assert_arg(args.first())?, assert_arg(args.get(1))?))
+}
+
+pub(crate) fn size(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::List(list)) => Ok(CelValue::Int(list.len() as i64)),
+        Some(CelValue::Map(map)) => Ok(CelValue::Int(map.len() as i64)),
+        Some(CelValue::String(s)) => Ok(CelValue::Int(s.chars().count() as i64)),
+        Some(CelValue::Bytes(b)) => Ok(CelValue::Int(b.len() as i64)),
+        Some(v) => Err(CelError::NoMatchingOverload(format!(
+            "Cannot apply 'size' to {:?}",
+            CelType::from(v)
+        ))),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+pub(crate) fn sum(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::List(list)) => {
+            let mut values = list.iter().cloned();
+            let first = values.next().unwrap_or(CelValue::Int(0));
+            values.try_fold(first, |acc, v| {
+                evaluate_arithmetic(ArithmeticOp::Add, acc, v)
+            })
+        }
+        Some(v) => Err(CelError::WrongArgumentType(CelType::List, CelType::from(v))),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+/// The largest scale a [Decimal] can represent.
+const MAX_SCALE: i128 = 28;
+
+pub(crate) fn round(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let d = decimal_arg(args.first())?;
+    let scale = match args.get(1) {
+        Some(CelValue::Int(i)) => i128::from(*i),
+        Some(CelValue::UInt(u)) => i128::from(*u),
+        Some(v) => return Err(CelError::WrongArgumentType(CelType::Int, CelType::from(v))),
+        None => 0,
+    };
+    if !(0..=MAX_SCALE).contains(&scale) {
+        return Err(CelError::DecimalError(format!(
+            "Scale {scale} is outside of 0..={MAX_SCALE}"
+        )));
+    }
+    let strategy = match args.get(2) {
+        Some(v) => rounding_strategy(&Arc::<String>::try_from(v)?)?,
+        None => RoundingStrategy::MidpointNearestEven,
+    };
     Ok(CelValue::Decimal(
-        s.parse()
-            .map_err(|e| CelError::DecimalError(format!("{e:?}")))?,
+        d.round_dp_with_strategy(scale as u32, strategy),
     ))
 }
 
+/// Maps the rounding mode names accepted by `round` (ie. 'HALF_EVEN', 'UP')
+/// onto a [RoundingStrategy].
+pub(crate) fn rounding_strategy(mode: &str) -> Result<RoundingStrategy, CelError> {
+    match mode {
+        "HALF_EVEN" => Ok(RoundingStrategy::MidpointNearestEven),
+        "HALF_UP" => Ok(RoundingStrategy::MidpointAwayFromZero),
+        "HALF_DOWN" => Ok(RoundingStrategy::MidpointTowardZero),
+        "UP" => Ok(RoundingStrategy::AwayFromZero),
+        "DOWN" => Ok(RoundingStrategy::ToZero),
+        "CEILING" => Ok(RoundingStrategy::ToPositiveInfinity),
+        "FLOOR" => Ok(RoundingStrategy::ToNegativeInfinity),
+        _ => Err(CelError::DecimalError(format!(
+            "Unknown rounding mode '{mode}'"
+        ))),
+    }
+}
+
+pub(crate) fn floor(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::Decimal(d)) => Ok(CelValue::Decimal(d.floor())),
+        Some(CelValue::Double(d)) => Ok(CelValue::Double(d.floor())),
+        Some(v @ (CelValue::Int(_) | CelValue::UInt(_))) => Ok(v.clone()),
+        Some(v) => Err(CelError::WrongArgumentType(
+            CelType::Decimal,
+            CelType::from(v),
+        )),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+pub(crate) fn ceil(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::Decimal(d)) => Ok(CelValue::Decimal(d.ceil())),
+        Some(CelValue::Double(d)) => Ok(CelValue::Double(d.ceil())),
+        Some(v @ (CelValue::Int(_) | CelValue::UInt(_))) => Ok(v.clone()),
+        Some(v) => Err(CelError::WrongArgumentType(
+            CelType::Decimal,
+            CelType::from(v),
+        )),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+pub(crate) fn abs(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    match args.first() {
+        Some(CelValue::Decimal(d)) => Ok(CelValue::Decimal(d.abs())),
+        Some(CelValue::Double(d)) => Ok(CelValue::Double(d.abs())),
+        Some(CelValue::Int(i)) => i
+            .checked_abs()
+            .map(CelValue::Int)
+            .ok_or_else(|| CelError::Overflow(format!("abs({i})"))),
+        Some(v @ CelValue::UInt(_)) => Ok(v.clone()),
+        Some(v) => Err(CelError::WrongArgumentType(
+            CelType::Decimal,
+            CelType::from(v),
+        )),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
+pub(crate) fn min(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    extremum(args, Ordering::Less)
+}
+
+pub(crate) fn max(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    extremum(args, Ordering::Greater)
+}
+
+/// Picks the smallest (`Less`) or largest (`Greater`) of the arguments,
+/// which may also be passed as a single list.
+/// Like arithmetic, integers are promoted to Decimal when any argument is one.
+fn extremum(args: Vec<CelValue>, keep: Ordering) -> Result<CelValue, CelError> {
+    let values: Vec<CelValue> = match args.as_slice() {
+        [CelValue::List(list)] => list.iter().cloned().collect(),
+        _ => args,
+    };
+    let op = match keep {
+        Ordering::Less => RelationOp::LessThan,
+        _ => RelationOp::GreaterThan,
+    };
+    let promote = values.iter().any(|v| matches!(v, CelValue::Decimal(_)));
+    let mut values = values.into_iter();
+    let first = values.next().ok_or(CelError::MissingArgument)?;
+    let result = values.try_fold(first, |acc, v| {
+        match evaluate_relation(op, v.clone(), acc.clone())? {
+            CelValue::Bool(true) => Ok::<_, CelError>(v),
+            _ => Ok(acc),
+        }
+    })?;
+    match result {
+        v @ (CelValue::Int(_) | CelValue::UInt(_)) if promote => {
+            Ok(CelValue::Decimal(decimal_arg(Some(&v))?))
+        }
+        v => Ok(v),
+    }
+}
+
+/// `percent(amount, bps)` returns `bps` basis points of `amount`.
+pub(crate) fn percent(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let amount = decimal_arg(args.first())?;
+    let bps = decimal_arg(args.get(1))?;
+    amount
+        .checked_mul(bps)
+        .and_then(|v| v.checked_div(Decimal::from(10_000)))
+        .map(CelValue::Decimal)
+        .ok_or_else(|| CelError::Overflow(format!("percent({amount}, {bps})")))
+}
+
+/// Reads a Decimal argument, accepting Int and UInt as well.
+pub(crate) fn decimal_arg(arg: Option<&CelValue>) -> Result<Decimal, CelError> {
+    match arg {
+        Some(CelValue::Decimal(d)) => Ok(*d),
+        Some(CelValue::Int(i)) => Ok(Decimal::from(*i)),
+        Some(CelValue::UInt(u)) => Ok(Decimal::from(*u)),
+        Some(v) => Err(CelError::WrongArgumentType(
+            CelType::Decimal,
+            CelType::from(v),
+        )),
+        None => Err(CelError::MissingArgument),
+    }
+}
+
 fn assert_arg<'a, T: TryFrom<&'a CelValue, Error = CelError>>(
     arg: Option<&'a CelValue>,
 ) -> Result<T, CelError> {
@@ -39,3 +368,112 @@ fn assert_arg<'a, T: TryFrom<&'a CelValue, Error = CelError>>(
         Err(CelError::MissingArgument)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{CelContext, CelError, CelExpression, CelValue};
+
+    fn eval(source: &str) -> CelValue {
+        source
+            .parse::<CelExpression>()
+            .unwrap()
+            .evaluate(&CelContext::new())
+            .unwrap()
+    }
+
+    fn dec(s: &str) -> CelValue {
+        CelValue::Decimal(s.parse().unwrap())
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(eval("round(2.345m, 2)"), dec("2.34"));
+        assert_eq!(eval("round(2.345m, 2, 'HALF_UP')"), dec("2.35"));
+        assert_eq!(eval("round(2.341m, 2, 'UP')"), dec("2.35"));
+        assert_eq!(eval("round(-2.349m, 2, 'DOWN')"), dec("-2.34"));
+        assert_eq!(eval("floor(-1.5m)"), dec("-2"));
+        assert_eq!(eval("ceil(1.2m)"), dec("2"));
+        assert_eq!(eval("abs(-1.2m)"), dec("1.2"));
+        assert_eq!(eval("abs(-3)"), CelValue::Int(3));
+        assert_eq!(eval("round(2.5m, 28)"), dec("2.5"));
+        for scale in ["29", "-1", "4294967297", "18446744073709551615u"] {
+            assert!(format!("round(2.5m, {scale})")
+                .parse::<CelExpression>()
+                .unwrap()
+                .evaluate(&CelContext::new())
+                .is_err());
+        }
+    }
+
+    fn string(s: &str) -> CelValue {
+        CelValue::String(s.to_string().into())
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(eval("'payout-' + string(42)"), string("payout-42"));
+        assert_eq!(eval("'payout'.startsWith('pay')"), CelValue::Bool(true));
+        assert_eq!(eval("'payout'.endsWith('pay')"), CelValue::Bool(false));
+        assert_eq!(eval("'payout'.contains('yo')"), CelValue::Bool(true));
+        assert_eq!(
+            eval("'ab-123'.matches('^[a-z]+-[0-9]+$')"),
+            CelValue::Bool(true)
+        );
+        assert_eq!(eval("'MiXeD'.lowerAscii()"), string("mixed"));
+        assert_eq!(eval("'MiXeD'.upperAscii()"), string("MIXED"));
+        assert_eq!(eval("'héllo'.size()"), CelValue::Int(5));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(eval("string(1.5m)"), string("1.5"));
+        assert_eq!(eval("string(true)"), string("true"));
+        assert_eq!(eval("int('-42')"), CelValue::Int(-42));
+        assert_eq!(eval("int(-2.7)"), CelValue::Int(-2));
+        assert_eq!(eval("uint(42)"), CelValue::UInt(42));
+        assert_eq!(eval("double(3)"), CelValue::Double(3.0));
+        assert_eq!(eval("decimal(7)"), dec("7"));
+        assert!("uint(-1)"
+            .parse::<CelExpression>()
+            .unwrap()
+            .evaluate(&CelContext::new())
+            .is_err());
+    }
+
+    #[test]
+    fn min_max() {
+        assert_eq!(eval("min(3, 1, 2)"), CelValue::Int(1));
+        assert_eq!(eval("max([1.5m, 2.5m])"), dec("2.5"));
+        assert_eq!(eval("max(0.5m, 1)"), dec("1"));
+        assert_eq!(eval("min([2u, 2.5m])"), dec("2"));
+    }
+
+    #[test]
+    fn overflow() {
+        for source in [
+            "abs(-9223372036854775807 - 1)",
+            "percent(79228162514264337593543950335m, 20000)",
+        ] {
+            let err = source
+                .parse::<CelExpression>()
+                .unwrap()
+                .evaluate(&CelContext::new())
+                .unwrap_err();
+            match err {
+                CelError::EvaluationError(_, e) => {
+                    assert!(matches!(*e, CelError::Overflow(_)), "{source}: {e}")
+                }
+                e => panic!("unexpected error {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn fee_calculation() {
+        assert_eq!(eval("percent(1000, 35)"), dec("3.5"));
+        assert_eq!(
+            eval("max(round(percent(200.00m, 35), 2, 'HALF_EVEN'), 1.00m)"),
+            dec("1.00")
+        );
+    }
+}
@@ -0,0 +1,507 @@
+use cel_parser::{
+    ast::{self, ArithmeticOp, Expression, Literal, UnaryOp},
+    RelationOp,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{cel_type::*, error::*, interpreter::CelExpression};
+
+/// Type declarations used to statically check a [CelExpression] without evaluating it.
+///
+/// A type of `None` means the type is only known at evaluation time.
+#[derive(Debug, Clone)]
+pub struct CelTypeEnv {
+    idents: HashMap<String, TypeDecl>,
+    functions: HashMap<String, Option<CelType>>,
+    message_types: HashMap<String, HashMap<String, CelType>>,
+}
+
+#[derive(Debug, Clone)]
+enum TypeDecl {
+    Value(Option<CelType>),
+    Record(HashMap<String, Option<CelType>>),
+}
+
+impl CelTypeEnv {
+    pub fn new() -> Self {
+        let mut functions = HashMap::new();
+        functions.insert("date".to_string(), Some(CelType::Date));
+        functions.insert("uuid".to_string(), Some(CelType::Uuid));
+        functions.insert("decimal".to_string(), Some(CelType::Decimal));
+        functions.insert("size".to_string(), Some(CelType::Int));
+        functions.insert("sum".to_string(), None);
+        functions.insert("round".to_string(), Some(CelType::Decimal));
+        functions.insert("floor".to_string(), None);
+        functions.insert("ceil".to_string(), None);
+        functions.insert("abs".to_string(), None);
+        functions.insert("min".to_string(), None);
+        functions.insert("max".to_string(), None);
+        functions.insert("percent".to_string(), Some(CelType::Decimal));
+        functions.insert("string".to_string(), Some(CelType::String));
+        functions.insert("int".to_string(), Some(CelType::Int));
+        functions.insert("uint".to_string(), Some(CelType::UInt));
+        functions.insert("double".to_string(), Some(CelType::Double));
+        for name in ["startsWith", "endsWith", "contains", "matches"] {
+            functions.insert(name.to_string(), Some(CelType::Bool));
+        }
+        for name in ["lowerAscii", "upperAscii"] {
+            functions.insert(name.to_string(), Some(CelType::String));
+        }
+        Self {
+            idents: HashMap::new(),
+            functions,
+            message_types: HashMap::new(),
+        }
+    }
+
+    pub fn add_variable(&mut self, name: impl Into<String>, t: Option<CelType>) {
+        self.idents.insert(name.into(), TypeDecl::Value(t));
+    }
+
+    /// Declares a map whose fields are known up front.
+    /// Selecting a field that wasn't declared is a type error.
+    pub fn add_record(
+        &mut self,
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = (String, Option<CelType>)>,
+    ) {
+        self.idents
+            .insert(name.into(), TypeDecl::Record(fields.into_iter().collect()));
+    }
+
+    /// Declares a function and its return type.
+    /// Calls to undeclared functions are rejected.
+    pub fn add_function(&mut self, name: impl Into<String>, return_type: Option<CelType>) {
+        self.functions.insert(name.into(), return_type);
+    }
+
+    /// Declares the fields of a message type.
+    /// Messages of undeclared types may have any fields.
+    pub fn add_message_type(
+        &mut self,
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = (String, CelType)>,
+    ) {
+        self.message_types
+            .insert(name.into(), fields.into_iter().collect());
+    }
+}
+
+impl Default for CelTypeEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of statically checking a [CelExpression].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CelTypeCheck {
+    pub result_type: Option<CelType>,
+    /// `(record, field)` pairs selected from records declared in the [CelTypeEnv].
+    pub field_references: HashSet<(String, String)>,
+}
+
+impl CelExpression {
+    pub fn check(&self, env: &CelTypeEnv) -> Result<CelTypeCheck, CelError> {
+        let mut field_references = HashSet::new();
+        let result_type = check_expression(&self.expr, env, &mut field_references)?;
+        Ok(CelTypeCheck {
+            result_type,
+            field_references,
+        })
+    }
+}
+
+type FieldReferences = HashSet<(String, String)>;
+
+fn check_expression(
+    expr: &Expression,
+    env: &CelTypeEnv,
+    refs: &mut FieldReferences,
+) -> Result<Option<CelType>, CelError> {
+    use Expression::*;
+    match expr {
+        Spanned(_, inner) => check_expression(inner, env, refs),
+        Ternary(cond, left, right) => {
+            expect_type(CelType::Bool, check_expression(cond, env, refs)?)?;
+            let left = check_expression(left, env, refs)?;
+            let right = check_expression(right, env, refs)?;
+            Ok(if left == right { left } else { None })
+        }
+        Relation(op, left, right) => {
+            let left = check_expression(left, env, refs)?;
+            let right = check_expression(right, env, refs)?;
+            if *op == RelationOp::In {
+                if let Some(t) = right {
+                    if t != CelType::List && t != CelType::Map {
+                        return Err(CelError::NoMatchingOverload(format!(
+                            "Cannot apply 'in' to {left:?} and {t:?}"
+                        )));
+                    }
+                }
+            }
+            Ok(Some(CelType::Bool))
+        }
+        Arithmetic(op, left, right) => {
+            let left = check_expression(left, env, refs)?;
+            let right = check_expression(right, env, refs)?;
+            check_arithmetic(*op, left, right)
+        }
+        Unary(op, expr) => {
+            let t = check_expression(expr, env, refs)?;
+            match op {
+                UnaryOp::Not | UnaryOp::DoubleNot => {
+                    expect_type(CelType::Bool, t)?;
+                    Ok(Some(CelType::Bool))
+                }
+                UnaryOp::Minus | UnaryOp::DoubleMinus => Ok(t),
+            }
+        }
+        Member(target, member) => check_member(target, member, env, refs),
+        List(exprs) => {
+            for e in exprs {
+                check_expression(e, env, refs)?;
+            }
+            Ok(Some(CelType::List))
+        }
+        Map(entries) => {
+            for (k, v) in entries {
+                check_expression(k, env, refs)?;
+                check_expression(v, env, refs)?;
+            }
+            Ok(Some(CelType::Map))
+        }
+        Struct(names, fields) => {
+            let type_name = names
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(".");
+            let declared = env.message_types.get(&type_name);
+            for (name, v) in fields {
+                let t = check_expression(v, env, refs)?;
+                if let Some(declared) = declared {
+                    match declared.get(name.as_str()) {
+                        Some(expected) => expect_type(*expected, t)?,
+                        None => return Err(CelError::UnknownField(format!("{type_name}.{name}"))),
+                    }
+                }
+            }
+            Ok(Some(CelType::Map))
+        }
+        Literal(l) => Ok(Some(literal_type(l))),
+        Ident(name) => match env.idents.get(name.as_str()) {
+            Some(TypeDecl::Value(t)) => Ok(*t),
+            Some(TypeDecl::Record(_)) => Ok(Some(CelType::Map)),
+            None => Err(CelError::UnknownIdent(name.to_string())),
+        },
+    }
+}
+
+fn check_member(
+    target: &Expression,
+    member: &ast::Member,
+    env: &CelTypeEnv,
+    refs: &mut FieldReferences,
+) -> Result<Option<CelType>, CelError> {
+    use ast::Member::*;
+    match (target.unspanned(), member) {
+        (Expression::Ident(name), Attribute(field)) => match env.idents.get(name.as_str()) {
+            Some(TypeDecl::Record(fields)) => match fields.get(field.as_str()) {
+                Some(t) => {
+                    refs.insert((name.to_string(), field.to_string()));
+                    Ok(*t)
+                }
+                None => Err(CelError::UnknownField(format!("{name}.{field}"))),
+            },
+            _ => check_attribute(check_expression(target, env, refs)?),
+        },
+        (Expression::Ident(name), FunctionCall(args)) if name.as_str() == "has" => {
+            match args
+                .iter()
+                .map(Expression::unspanned)
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                [Expression::Member(target, attr)] if matches!(attr.as_ref(), Attribute(_)) => {
+                    check_attribute(check_expression(target, env, refs)?)?;
+                    Ok(Some(CelType::Bool))
+                }
+                _ => Err(CelError::InvalidMacro(
+                    "has() requires a field selection".to_string(),
+                )),
+            }
+        }
+        (Expression::Ident(name), FunctionCall(args)) => {
+            for e in args {
+                check_expression(e, env, refs)?;
+            }
+            check_function(name, env)
+        }
+        (Expression::Member(receiver, attr), FunctionCall(args)) => match attr.as_ref() {
+            Attribute(name) => check_method(receiver, name, args, env, refs),
+            _ => Err(CelError::IllegalTarget),
+        },
+        (_, Attribute(_)) => check_attribute(check_expression(target, env, refs)?),
+        (_, Index(idx)) => {
+            let target = check_expression(target, env, refs)?;
+            check_expression(idx, env, refs)?;
+            match target {
+                None | Some(CelType::List) | Some(CelType::Map) => Ok(None),
+                Some(t) => Err(CelError::BadType(CelType::List, t)),
+            }
+        }
+        (_, FunctionCall(_)) => Err(CelError::IllegalTarget),
+    }
+}
+
+fn check_method(
+    receiver: &Expression,
+    name: &Arc<String>,
+    args: &[Expression],
+    env: &CelTypeEnv,
+    refs: &mut FieldReferences,
+) -> Result<Option<CelType>, CelError> {
+    let receiver_type = check_expression(receiver, env, refs)?;
+    let var = match args.first().map(Expression::unspanned) {
+        Some(Expression::Ident(var)) => Some(var),
+        _ => None,
+    };
+    match (name.as_str(), var, args) {
+        ("all" | "exists" | "exists_one" | "filter", Some(var), [_, predicate]) => {
+            check_range(receiver_type)?;
+            let mut scope = env.clone();
+            scope.add_variable(var.as_str(), None);
+            expect_type(CelType::Bool, check_expression(predicate, &scope, refs)?)?;
+            if name.as_str() == "filter" {
+                Ok(Some(CelType::List))
+            } else {
+                Ok(Some(CelType::Bool))
+            }
+        }
+        ("map", Some(var), [_, rest @ ..]) if !rest.is_empty() && rest.len() <= 2 => {
+            check_range(receiver_type)?;
+            let mut scope = env.clone();
+            scope.add_variable(var.as_str(), None);
+            if let [predicate, _] = rest {
+                expect_type(CelType::Bool, check_expression(predicate, &scope, refs)?)?;
+            }
+            check_expression(rest.last().expect("rest is not empty"), &scope, refs)?;
+            Ok(Some(CelType::List))
+        }
+        ("all" | "exists" | "exists_one" | "filter" | "map", _, _) => Err(CelError::InvalidMacro(
+            format!("Unsupported arguments for '{name}'"),
+        )),
+        _ => {
+            for e in args {
+                check_expression(e, env, refs)?;
+            }
+            check_function(name, env)
+        }
+    }
+}
+
+fn check_function(name: &str, env: &CelTypeEnv) -> Result<Option<CelType>, CelError> {
+    match env.functions.get(name) {
+        Some(t) => Ok(*t),
+        None => Err(CelError::UnknownIdent(name.to_string())),
+    }
+}
+
+fn check_range(t: Option<CelType>) -> Result<(), CelError> {
+    match t {
+        None | Some(CelType::List) | Some(CelType::Map) => Ok(()),
+        Some(t) => Err(CelError::BadType(CelType::List, t)),
+    }
+}
+
+fn check_attribute(target: Option<CelType>) -> Result<Option<CelType>, CelError> {
+    match target {
+        None | Some(CelType::Map) => Ok(None),
+        Some(t) => Err(CelError::BadType(CelType::Map, t)),
+    }
+}
+
+fn check_arithmetic(
+    op: ArithmeticOp,
+    left: Option<CelType>,
+    right: Option<CelType>,
+) -> Result<Option<CelType>, CelError> {
+    use CelType::*;
+    match (left, right) {
+        (Some(l), Some(r)) if l == r && matches!(l, Int | UInt | Double | Decimal) => Ok(Some(l)),
+        (Some(l), Some(r))
+            if l == r && op == ArithmeticOp::Add && matches!(l, String | Bytes | List) =>
+        {
+            Ok(Some(l))
+        }
+        (Some(Decimal), Some(Int | UInt)) | (Some(Int | UInt), Some(Decimal)) => Ok(Some(Decimal)),
+        (Some(l), Some(r)) => Err(CelError::NoMatchingOverload(format!(
+            "Cannot apply '{op:?}' to {l:?} and {r:?}"
+        ))),
+        // Integers are promoted when the other operand is a Decimal,
+        // so only a known Decimal operand determines the result
+        (Some(Decimal), None) | (None, Some(Decimal)) => Ok(Some(Decimal)),
+        (_, None) | (None, _) => Ok(None),
+    }
+}
+
+fn expect_type(expected: CelType, found: Option<CelType>) -> Result<(), CelError> {
+    match found {
+        Some(t) if t != expected => Err(CelError::BadType(expected, t)),
+        _ => Ok(()),
+    }
+}
+
+fn literal_type(l: &Literal) -> CelType {
+    match l {
+        Literal::Int(_) => CelType::Int,
+        Literal::UInt(_) => CelType::UInt,
+        Literal::Double(_) => CelType::Double,
+        Literal::Decimal(_) => CelType::Decimal,
+        Literal::String(_) => CelType::String,
+        Literal::Bytes(_) => CelType::Bytes,
+        Literal::Bool(_) => CelType::Bool,
+        Literal::Null => CelType::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> CelTypeEnv {
+        let mut env = CelTypeEnv::new();
+        env.add_variable("SETTLED", Some(CelType::String));
+        env.add_record(
+            "params",
+            vec![
+                ("amount".to_string(), Some(CelType::Decimal)),
+                ("account".to_string(), Some(CelType::Uuid)),
+                ("items".to_string(), None),
+            ],
+        );
+        env.add_message_type(
+            "Metadata",
+            vec![
+                ("order_id".to_string(), CelType::Uuid),
+                ("channel".to_string(), CelType::String),
+            ],
+        );
+        env
+    }
+
+    fn check(source: &str) -> Result<CelTypeCheck, CelError> {
+        source.parse::<CelExpression>().unwrap().check(&env())
+    }
+
+    #[test]
+    fn infers_types() {
+        assert_eq!(
+            check("params.amount").unwrap().result_type,
+            Some(CelType::Decimal)
+        );
+        assert_eq!(
+            check("decimal('1') + params.amount").unwrap().result_type,
+            Some(CelType::Decimal)
+        );
+        assert_eq!(
+            check("uuid('00000000-0000-0000-0000-000000000001')")
+                .unwrap()
+                .result_type,
+            Some(CelType::Uuid)
+        );
+        assert_eq!(check("SETTLED").unwrap().result_type, Some(CelType::String));
+        assert_eq!(
+            check("params.items.exists(i, i.amount > 1)")
+                .unwrap()
+                .result_type,
+            Some(CelType::Bool)
+        );
+        assert_eq!(check("params.items[0]").unwrap().result_type, None);
+    }
+
+    #[test]
+    fn arithmetic_with_unknown_operand() {
+        assert_eq!(check("abs(params.amount) * 2").unwrap().result_type, None);
+        assert_eq!(
+            check("max(params.amount, 1m) + 1").unwrap().result_type,
+            None
+        );
+        assert_eq!(check("sum([1.5m, 2m]) * 2").unwrap().result_type, None);
+        assert_eq!(
+            check("params.items[0] * params.amount")
+                .unwrap()
+                .result_type,
+            Some(CelType::Decimal)
+        );
+    }
+
+    #[test]
+    fn records_field_references() {
+        let res = check("params.amount > decimal('0') ? params.amount : params.amount").unwrap();
+        assert_eq!(
+            res.field_references,
+            HashSet::from([("params".to_string(), "amount".to_string())])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_idents_and_fields() {
+        assert!(matches!(
+            check("param.amount"),
+            Err(CelError::UnknownIdent(_))
+        ));
+        assert!(matches!(
+            check("params.fee"),
+            Err(CelError::UnknownField(_))
+        ));
+        assert!(matches!(
+            check("rond(params.amount, 2)"),
+            Err(CelError::UnknownIdent(name)) if name == "rond"
+        ));
+        assert!(matches!(
+            check("'a'.startswith('b')"),
+            Err(CelError::UnknownIdent(name)) if name == "startswith"
+        ));
+    }
+
+    #[test]
+    fn promotes_integers_to_decimal() {
+        assert_eq!(
+            check("params.amount * 2").unwrap().result_type,
+            Some(CelType::Decimal)
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        assert!(check("params.amount + 'fee'").is_err());
+        assert!(check("params.amount + 1.5").is_err());
+        assert!(check("params.amount ? 1 : 2").is_err());
+        assert!(check("params.account.id").is_err());
+    }
+
+    #[test]
+    fn checks_message_fields() {
+        assert_eq!(
+            check("Metadata{order_id: params.account, channel: 'web'}")
+                .unwrap()
+                .result_type,
+            Some(CelType::Map)
+        );
+        assert!(matches!(
+            check("Metadata{order: params.account}"),
+            Err(CelError::UnknownField(_))
+        ));
+        assert!(matches!(
+            check("Metadata{channel: params.amount}"),
+            Err(CelError::BadType(CelType::String, CelType::Decimal))
+        ));
+        assert!(check("Other{anything: 1}").is_ok());
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use std::sync::Arc;
+
+/// Source of the current time used by `date()`.
+///
+/// Defaults to the system clock in UTC. Tests can freeze it with [CelClock::fixed].
+#[derive(Clone)]
+pub struct CelClock {
+    now: Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>,
+    timezone: Tz,
+}
+
+impl CelClock {
+    /// The system clock.
+    pub fn system() -> Self {
+        Self::from_fn(Utc::now)
+    }
+
+    /// A clock that always returns `at`.
+    pub fn fixed(at: DateTime<Utc>) -> Self {
+        Self::from_fn(move || at)
+    }
+
+    pub fn from_fn(now: impl Fn() -> DateTime<Utc> + Send + Sync + 'static) -> Self {
+        Self {
+            now: Arc::new(now),
+            timezone: Tz::UTC,
+        }
+    }
+
+    /// Sets the timezone that determines the current date, ie. `America/El_Salvador`.
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        (self.now)()
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// The current date in the clock's timezone.
+    pub fn today(&self) -> NaiveDate {
+        self.now().with_timezone(&self.timezone).date_naive()
+    }
+}
+
+impl Default for CelClock {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+impl std::fmt::Debug for CelClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CelClock")
+            .field("now", &self.now())
+            .field("timezone", &self.timezone)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn today_in_timezone() {
+        let at = "2024-03-01T03:30:00Z".parse().unwrap();
+        let clock = CelClock::fixed(at);
+        assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        let clock = clock.with_timezone(chrono_tz::America::El_Salvador);
+        assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+    }
+}
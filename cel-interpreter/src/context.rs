@@ -1,29 +1,112 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use crate::{builtins, error::*, value::*};
+use crate::{builtins, cel_type::*, clock::*, error::*, plan::PURE_FUNCTIONS, value::*};
 
-type CelFunction = Box<dyn Fn(Vec<CelValue>) -> Result<CelValue, CelError>>;
-#[derive(Debug)]
+pub type CelFunction = Arc<dyn Fn(Vec<CelValue>) -> Result<CelValue, CelError> + Send + Sync>;
+#[derive(Debug, Clone)]
 pub struct CelContext {
     idents: HashMap<String, ContextItem>,
+    message_types: HashMap<String, HashMap<String, CelType>>,
+    /// Pure builtins replaced by a variable or function of the same name.
+    shadowed: HashSet<String>,
 }
 
 impl CelContext {
     pub fn new() -> Self {
         let mut idents = HashMap::new();
-        idents.insert(
-            "date".to_string(),
-            ContextItem::Function(Box::new(builtins::date)),
-        );
+        idents.insert("date".to_string(), date_function(CelClock::default()));
         idents.insert(
             "uuid".to_string(),
-            ContextItem::Function(Box::new(builtins::uuid)),
+            ContextItem::Function(Arc::new(builtins::uuid)),
         );
         idents.insert(
             "decimal".to_string(),
-            ContextItem::Function(Box::new(builtins::decimal)),
+            ContextItem::Function(Arc::new(builtins::decimal)),
+        );
+        idents.insert(
+            "size".to_string(),
+            ContextItem::Function(Arc::new(builtins::size)),
+        );
+        idents.insert(
+            "sum".to_string(),
+            ContextItem::Function(Arc::new(builtins::sum)),
+        );
+        idents.insert(
+            "round".to_string(),
+            ContextItem::Function(Arc::new(builtins::round)),
         );
-        Self { idents }
+        idents.insert(
+            "floor".to_string(),
+            ContextItem::Function(Arc::new(builtins::floor)),
+        );
+        idents.insert(
+            "ceil".to_string(),
+            ContextItem::Function(Arc::new(builtins::ceil)),
+        );
+        idents.insert(
+            "abs".to_string(),
+            ContextItem::Function(Arc::new(builtins::abs)),
+        );
+        idents.insert(
+            "min".to_string(),
+            ContextItem::Function(Arc::new(builtins::min)),
+        );
+        idents.insert(
+            "max".to_string(),
+            ContextItem::Function(Arc::new(builtins::max)),
+        );
+        idents.insert(
+            "percent".to_string(),
+            ContextItem::Function(Arc::new(builtins::percent)),
+        );
+        idents.insert(
+            "string".to_string(),
+            ContextItem::Function(Arc::new(builtins::string)),
+        );
+        idents.insert(
+            "int".to_string(),
+            ContextItem::Function(Arc::new(builtins::int)),
+        );
+        idents.insert(
+            "uint".to_string(),
+            ContextItem::Function(Arc::new(builtins::uint)),
+        );
+        idents.insert(
+            "double".to_string(),
+            ContextItem::Function(Arc::new(builtins::double)),
+        );
+        idents.insert(
+            "startsWith".to_string(),
+            ContextItem::Function(Arc::new(builtins::starts_with)),
+        );
+        idents.insert(
+            "endsWith".to_string(),
+            ContextItem::Function(Arc::new(builtins::ends_with)),
+        );
+        idents.insert(
+            "contains".to_string(),
+            ContextItem::Function(Arc::new(builtins::contains)),
+        );
+        idents.insert(
+            "matches".to_string(),
+            ContextItem::Function(Arc::new(builtins::matches)),
+        );
+        idents.insert(
+            "lowerAscii".to_string(),
+            ContextItem::Function(Arc::new(builtins::lower_ascii)),
+        );
+        idents.insert(
+            "upperAscii".to_string(),
+            ContextItem::Function(Arc::new(builtins::upper_ascii)),
+        );
+        Self {
+            idents,
+            message_types: HashMap::new(),
+            shadowed: HashSet::new(),
+        }
     }
 }
 impl Default for CelContext {
@@ -32,6 +115,11 @@ impl Default for CelContext {
     }
 }
 
+fn date_function(clock: CelClock) -> ContextItem {
+    ContextItem::Function(Arc::new(move |args| builtins::date(&clock, args)))
+}
+
+#[derive(Clone)]
 pub(crate) enum ContextItem {
     Value(CelValue),
     Function(CelFunction),
@@ -47,14 +135,65 @@ impl std::fmt::Debug for ContextItem {
 }
 
 impl CelContext {
-    pub(crate) fn lookup(&self, name: Arc<String>) -> Result<&ContextItem, CelError> {
-        self.idents
-            .get(name.as_ref())
-            .ok_or_else(|| CelError::UnknownIdent(name.to_string()))
+    pub(crate) fn get(&self, name: &str) -> Option<&ContextItem> {
+        self.idents.get(name)
+    }
+
+    /// Whether `name` no longer refers to the pure builtin of that name.
+    pub(crate) fn shadows(&self, name: &str) -> bool {
+        self.shadowed.contains(name)
     }
 
     pub fn add_variable(&mut self, name: impl Into<String>, value: impl Into<CelValue>) {
-        self.idents
-            .insert(name.into(), ContextItem::Value(value.into()));
+        self.insert(name.into(), ContextItem::Value(value.into()));
+    }
+
+    pub fn add_function(
+        &mut self,
+        name: impl Into<String>,
+        f: impl Fn(Vec<CelValue>) -> Result<CelValue, CelError> + Send + Sync + 'static,
+    ) {
+        self.insert(name.into(), ContextItem::Function(Arc::new(f)));
+    }
+
+    fn insert(&mut self, name: String, item: ContextItem) {
+        if PURE_FUNCTIONS.contains(&name.as_str()) {
+            self.shadowed.insert(name.clone());
+        }
+        self.idents.insert(name, item);
+    }
+
+    /// Sets the clock (and thereby the timezone) `date()` takes the current date from.
+    pub fn set_clock(&mut self, clock: CelClock) {
+        self.idents.insert("date".to_string(), date_function(clock));
+    }
+
+    /// Registers the fields of a message type, ie. `Metadata{order_id: ...}`.
+    /// Constructing a registered message with an undeclared field or a value
+    /// of the wrong type fails. Unregistered messages accept any field.
+    pub fn add_message_type(
+        &mut self,
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = (String, CelType)>,
+    ) {
+        self.message_types
+            .insert(name.into(), fields.into_iter().collect());
+    }
+
+    /// Checks the value of a field against the registered message type, if any.
+    pub(crate) fn check_message_field(
+        &self,
+        type_name: &str,
+        field: &str,
+        value: &CelValue,
+    ) -> Result<(), CelError> {
+        let Some(fields) = self.message_types.get(type_name) else {
+            return Ok(());
+        };
+        match fields.get(field) {
+            Some(expected) if *expected == CelType::from(value) => Ok(()),
+            Some(expected) => Err(CelError::BadType(*expected, CelType::from(value))),
+            None => Err(CelError::UnknownField(format!("{type_name}.{field}"))),
+        }
     }
 }
@@ -1,3 +1,4 @@
+use cel_parser::ast::Span;
 use chrono::ParseError;
 use thiserror::Error;
 
@@ -5,12 +6,14 @@ use crate::cel_type::*;
 
 #[derive(Error, Debug)]
 pub enum CelError {
-    #[error("CelError - CelParseError: {0}")]
-    CelParseError(String),
+    #[error("CelError - CelParseError: {0} at {1}\n{}", .1.highlight())]
+    CelParseError(String, CelSourceLocation),
     #[error("CelError - BadType: expected {0:?} found {1:?}")]
     BadType(CelType, CelType),
     #[error("CelError - UnknownIdentifier: {0}")]
     UnknownIdent(String),
+    #[error("CelError - UnknownField: {0}")]
+    UnknownField(String),
     #[error("CelError - IllegalTarget")]
     IllegalTarget,
     #[error("CelError - MissingArgument")]
@@ -23,11 +26,81 @@ pub enum CelError {
     UuidError(String),
     #[error("CelError - DecimalError: {0}")]
     DecimalError(String),
+    #[error("CelError - UnknownCurrency: {0}")]
+    UnknownCurrency(String),
+    #[error("CelError - IndexOutOfBounds: {0} for list of size {1}")]
+    IndexOutOfBounds(String, usize),
+    #[error("CelError - InvalidConversion: {0}")]
+    InvalidConversion(String),
+    #[error("CelError - InvalidRegex: {0}")]
+    InvalidRegex(String),
+    #[error("CelError - InvalidMacro: {0}")]
+    InvalidMacro(String),
+    #[error("CelError - Overflow: {0}")]
+    Overflow(String),
+    #[error("CelError - DivisionByZero")]
+    DivisionByZero,
+    #[error("CelError - BudgetExceeded: {0}")]
+    BudgetExceeded(String),
     #[error("CelError - NoMatchingOverload: {0}")]
     NoMatchingOverload(String),
     #[error("CelError - Unexpected: {0}")]
     Unexpected(String),
 
-    #[error("Error evaluating cell expression '{0}' - {1}")]
-    EvaluationError(String, Box<Self>),
+    #[error("Error evaluating cel expression at {0} - {1}\n{}", .0.highlight())]
+    EvaluationError(CelSourceLocation, Box<Self>),
+}
+
+/// Position of an error within the source of a cel expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CelSourceLocation {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column (in characters) within the line.
+    pub column: usize,
+    /// The offending part of the source.
+    pub snippet: String,
+    source_line: String,
+    width: usize,
+}
+
+impl CelSourceLocation {
+    pub(crate) fn new(source: &str, span: Span) -> Self {
+        let mut start = span.start.min(source.len());
+        while !source.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = span.end.clamp(start, source.len());
+        while !source.is_char_boundary(end) {
+            end += 1;
+        }
+        let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = source[start..]
+            .find('\n')
+            .map(|i| start + i)
+            .unwrap_or(source.len());
+        Self {
+            line: source[..start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            snippet: source[start..end].to_string(),
+            source_line: source[line_start..line_end].to_string(),
+            width: source[start..end.min(line_end)].chars().count().max(1),
+        }
+    }
+
+    /// Renders the source line with a caret marker under the offending snippet.
+    pub fn highlight(&self) -> String {
+        format!(
+            "  {}\n  {}{}",
+            self.source_line,
+            " ".repeat(self.column - 1),
+            "^".repeat(self.width)
+        )
+    }
+}
+
+impl std::fmt::Display for CelSourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
 }
@@ -1,20 +1,22 @@
 use serde::{Deserialize, Serialize};
 
 use cel_parser::{
-    ast::{self, ArithmeticOp, Expression, RelationOp},
+    ast::{ArithmeticOp, Expression, RelationOp, Span, UnaryOp},
     parser::ExpressionParser,
+    ParseError,
 };
 
 use std::sync::Arc;
 
-use crate::{cel_type::*, context::*, error::*, value::*};
+use crate::{budget::*, cel_type::*, context::*, error::*, plan::*, value::*};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(try_from = "String")]
 #[serde(into = "String")]
 pub struct CelExpression {
     source: String,
-    expr: Expression,
+    pub(crate) expr: Expression,
+    plan: Arc<Plan>,
 }
 impl CelExpression {
     pub fn try_evaluate<'a, T: TryFrom<CelResult<'a>, Error = E>, E: From<CelError>>(
@@ -23,201 +25,150 @@ impl CelExpression {
     ) -> Result<T, E> {
         let res = self.evaluate(ctx)?;
         T::try_from(CelResult {
-            expr: &self.expr,
+            expr: self,
             val: res,
         })
     }
 
     pub fn evaluate(&self, ctx: &CelContext) -> Result<CelValue, CelError> {
-        match evaluate_expression(&self.expr, ctx)? {
-            EvalType::Value(val) => Ok(val),
-            EvalType::ContextItem(ContextItem::Value(val)) => Ok(val.clone()),
-            _ => Err(CelError::Unexpected(
-                "evaluate didn't return a value".to_string(),
-            )),
-        }
+        self.evaluate_with_budget(ctx, &CelBudget::default())
     }
-}
 
-enum EvalType<'a> {
-    Value(CelValue),
-    ContextItem(&'a ContextItem),
-}
-
-impl<'a> EvalType<'a> {
-    fn try_bool(&self) -> Result<bool, CelError> {
-        if let EvalType::Value(val) = self {
-            val.try_bool()
-        } else {
-            Err(CelError::Unexpected(
-                "Expression didn't resolve to a bool".to_string(),
-            ))
-        }
+    /// Evaluates the expression, failing with [CelError::BudgetExceeded]
+    /// once it consumes more than `budget` allows.
+    pub fn evaluate_with_budget(
+        &self,
+        ctx: &CelContext,
+        budget: &CelBudget,
+    ) -> Result<CelValue, CelError> {
+        let meter = Meter::new(budget);
+        self.plan
+            .evaluate(ctx, &meter)
+            .map_err(|EvalError { span, error }| {
+                CelError::EvaluationError(self.location(span), Box::new(error))
+            })
     }
 
-    fn try_key(&self) -> Result<CelKey, CelError> {
-        if let EvalType::Value(val) = self {
-            match val {
-                CelValue::Int(i) => Ok(CelKey::Int(*i)),
-                CelValue::UInt(u) => Ok(CelKey::UInt(*u)),
-                CelValue::Bool(b) => Ok(CelKey::Bool(*b)),
-                CelValue::String(s) => Ok(CelKey::String(s.clone())),
-                _ => Err(CelError::Unexpected(
-                    "Expression didn't resolve to a valid key".to_string(),
-                )),
-            }
-        } else {
-            Err(CelError::Unexpected(
-                "Expression didn't resolve to value".to_string(),
-            ))
-        }
+    /// Names of the variables and functions the expression looks up in the context.
+    pub fn identifiers(&self) -> impl Iterator<Item = &str> {
+        self.plan.globals()
     }
 
-    fn try_value(&self) -> Result<CelValue, CelError> {
-        if let EvalType::Value(val) = self {
-            Ok(val.clone())
-        } else {
-            Err(CelError::Unexpected("Couldn't unwrap value".to_string()))
-        }
+    /// Location of `span` within the source, defaulting to the whole expression.
+    pub(crate) fn location(&self, span: Option<Span>) -> CelSourceLocation {
+        CelSourceLocation::new(
+            &self.source,
+            span.unwrap_or(Span {
+                start: 0,
+                end: self.source.len(),
+            }),
+        )
     }
 }
 
-fn evaluate_expression<'a>(
-    expr: &Expression,
-    ctx: &'a CelContext,
-) -> Result<EvalType<'a>, CelError> {
-    match evaluate_expression_inner(expr, ctx) {
-        Ok(val) => Ok(val),
-        Err(e) => Err(CelError::EvaluationError(format!("{expr:?}"), Box::new(e))),
+/// Promotes Int and UInt operands to Decimal when the other operand is a Decimal.
+fn promote(left: CelValue, right: CelValue) -> (CelValue, CelValue) {
+    use CelValue::*;
+    match (left, right) {
+        (Decimal(l), Int(r)) => (Decimal(l), Decimal(r.into())),
+        (Decimal(l), UInt(r)) => (Decimal(l), Decimal(r.into())),
+        (Int(l), Decimal(r)) => (Decimal(l.into()), Decimal(r)),
+        (UInt(l), Decimal(r)) => (Decimal(l.into()), Decimal(r)),
+        operands => operands,
     }
 }
 
-fn evaluate_expression_inner<'a>(
-    expr: &Expression,
-    ctx: &'a CelContext,
-) -> Result<EvalType<'a>, CelError> {
-    use Expression::*;
-    match expr {
-        Ternary(cond, left, right) => {
-            if evaluate_expression(cond, ctx)?.try_bool()? {
-                evaluate_expression(left, ctx)
-            } else {
-                evaluate_expression(right, ctx)
+/// Applies a checked integer or Decimal operation,
+/// mapping `None` to [CelError::DivisionByZero] or [CelError::Overflow].
+macro_rules! checked {
+    ($op:expr, $l:expr, $r:expr, $zero:expr) => {{
+        let (l, r) = ($l, $r);
+        match $op {
+            ArithmeticOp::Divide | ArithmeticOp::Modulus if r == $zero => {
+                Err(CelError::DivisionByZero)
             }
-        }
-        Member(expr, member) => {
-            let ident = evaluate_expression(expr, ctx)?;
-            evaluate_member(ident, member, ctx)
-        }
-        Map(entries) => {
-            let mut map = CelMap::new();
-            for (k, v) in entries {
-                let key = evaluate_expression(k, ctx)?;
-                let value = evaluate_expression(v, ctx)?;
-                map.insert(key.try_key()?, value.try_value()?)
+            op => match op {
+                ArithmeticOp::Add => l.checked_add(r),
+                ArithmeticOp::Subtract => l.checked_sub(r),
+                ArithmeticOp::Multiply => l.checked_mul(r),
+                ArithmeticOp::Divide => l.checked_div(r),
+                ArithmeticOp::Modulus => l.checked_rem(r),
             }
-            Ok(EvalType::Value(CelValue::from(map)))
+            .ok_or_else(|| CelError::Overflow(format!("{l} {} {r}", symbol(op)))),
         }
-        Ident(name) => Ok(EvalType::ContextItem(ctx.lookup(Arc::clone(name))?)),
-        Literal(val) => Ok(EvalType::Value(CelValue::from(val))),
-        Arithmetic(op, left, right) => {
-            let left = evaluate_expression(left, ctx)?;
-            let right = evaluate_expression(right, ctx)?;
-            Ok(EvalType::Value(evaluate_arithmetic(
-                *op,
-                left.try_value()?,
-                right.try_value()?,
-            )?))
+    }};
+}
+
+pub(crate) fn evaluate_arithmetic(
+    op: ArithmeticOp,
+    left: CelValue,
+    right: CelValue,
+) -> Result<CelValue, CelError> {
+    let (left, right) = promote(left, right);
+    use CelValue::*;
+    match (&left, &right) {
+        (UInt(l), UInt(r)) => checked!(op, *l, *r, 0).map(UInt),
+        (Int(l), Int(r)) => checked!(op, *l, *r, 0).map(Int),
+        (Decimal(l), Decimal(r)) => checked!(op, *l, *r, rust_decimal::Decimal::ZERO).map(Decimal),
+        (Double(l), Double(r)) if op != ArithmeticOp::Modulus => Ok(Double(match op {
+            ArithmeticOp::Add => l + r,
+            ArithmeticOp::Subtract => l - r,
+            ArithmeticOp::Multiply => l * r,
+            _ => l / r,
+        })),
+        (String(l), String(r)) if op == ArithmeticOp::Add => {
+            Ok(String(Arc::new(format!("{l}{r}"))))
         }
-        Relation(op, left, right) => {
-            let left = evaluate_expression(left, ctx)?;
-            let right = evaluate_expression(right, ctx)?;
-            Ok(EvalType::Value(evaluate_relation(
-                *op,
-                left.try_value()?,
-                right.try_value()?,
-            )?))
+        (Bytes(l), Bytes(r)) if op == ArithmeticOp::Add => {
+            Ok(Bytes(Arc::new([l.as_slice(), r.as_slice()].concat())))
         }
-        e => Err(CelError::Unexpected(format!("unimplemented {e:?}"))),
+        (List(l), List(r)) if op == ArithmeticOp::Add => Ok(List(Arc::new(
+            l.iter().chain(r.iter()).cloned().collect::<Vec<_>>().into(),
+        ))),
+        _ => Err(CelError::NoMatchingOverload(format!(
+            "Cannot apply '{}' to {:?} and {:?}",
+            symbol(op),
+            CelType::from(&left),
+            CelType::from(&right)
+        ))),
     }
 }
 
-fn evaluate_member<'a>(
-    target: EvalType,
-    member: &ast::Member,
-    ctx: &CelContext,
-) -> Result<EvalType<'a>, CelError> {
-    use ast::Member::*;
-    match member {
-        Attribute(name) => match target {
-            EvalType::ContextItem(ContextItem::Value(CelValue::Map(map))) => {
-                Ok(EvalType::Value(map.get(name)))
-            }
-            _ => Err(CelError::IllegalTarget),
-        },
-        FunctionCall(exprs) => match target {
-            EvalType::ContextItem(ContextItem::Function(f)) => {
-                let mut args = Vec::new();
-                for e in exprs {
-                    args.push(evaluate_expression(e, ctx)?.try_value()?)
-                }
-                Ok(EvalType::Value(f(args)?))
-            }
-            _ => Err(CelError::IllegalTarget),
-        },
-        _ => unimplemented!(),
+fn symbol(op: ArithmeticOp) -> &'static str {
+    match op {
+        ArithmeticOp::Add => "+",
+        ArithmeticOp::Subtract => "-",
+        ArithmeticOp::Multiply => "*",
+        ArithmeticOp::Divide => "/",
+        ArithmeticOp::Modulus => "%",
     }
 }
 
-fn evaluate_arithmetic(
-    op: ArithmeticOp,
-    left: CelValue,
-    right: CelValue,
-) -> Result<CelValue, CelError> {
+pub(crate) fn evaluate_unary(op: &UnaryOp, val: CelValue) -> Result<CelValue, CelError> {
     use CelValue::*;
-    match op {
-        ArithmeticOp::Multiply => match (&left, &right) {
-            (UInt(l), UInt(r)) => Ok(UInt(l * r)),
-            (Int(l), Int(r)) => Ok(Int(l * r)),
-            (Double(l), Double(r)) => Ok(Double(l * r)),
-            (Decimal(l), Decimal(r)) => Ok(Decimal(l * r)),
-            _ => Err(CelError::NoMatchingOverload(format!(
-                "Cannot apply '*' to {:?} and {:?}",
-                CelType::from(&left),
-                CelType::from(&right)
-            ))),
-        },
-        ArithmeticOp::Add => match (&left, &right) {
-            (UInt(l), UInt(r)) => Ok(UInt(l + r)),
-            (Int(l), Int(r)) => Ok(Int(l + r)),
-            (Double(l), Double(r)) => Ok(Double(l + r)),
-            (Decimal(l), Decimal(r)) => Ok(Decimal(l + r)),
-            _ => Err(CelError::NoMatchingOverload(format!(
-                "Cannot apply '+' to {:?} and {:?}",
-                CelType::from(&left),
-                CelType::from(&right)
-            ))),
-        },
-        ArithmeticOp::Subtract => match (&left, &right) {
-            (UInt(l), UInt(r)) => Ok(UInt(l - r)),
-            (Int(l), Int(r)) => Ok(Int(l - r)),
-            (Double(l), Double(r)) => Ok(Double(l - r)),
-            (Decimal(l), Decimal(r)) => Ok(Decimal(l - r)),
-            _ => Err(CelError::NoMatchingOverload(format!(
-                "Cannot apply '-' to {:?} and {:?}",
-                CelType::from(&left),
-                CelType::from(&right)
-            ))),
-        },
-        _ => unimplemented!(),
+    match (op, val) {
+        (UnaryOp::Not, Bool(b)) => Ok(Bool(!b)),
+        (UnaryOp::DoubleNot, Bool(b)) => Ok(Bool(b)),
+        (UnaryOp::Minus, Int(i)) => i
+            .checked_neg()
+            .map(Int)
+            .ok_or_else(|| CelError::Overflow(format!("-({i})"))),
+        (UnaryOp::Minus, Double(d)) => Ok(Double(-d)),
+        (UnaryOp::Minus, Decimal(d)) => Ok(Decimal(-d)),
+        (UnaryOp::DoubleMinus, v @ (Int(_) | Double(_) | Decimal(_))) => Ok(v),
+        (op, v) => Err(CelError::NoMatchingOverload(format!(
+            "Cannot apply '{op:?}' to {:?}",
+            CelType::from(&v)
+        ))),
     }
 }
 
-fn evaluate_relation(
+pub(crate) fn evaluate_relation(
     op: RelationOp,
     left: CelValue,
     right: CelValue,
 ) -> Result<CelValue, CelError> {
+    let (left, right) = promote(left, right);
     use CelValue::*;
     match op {
         RelationOp::LessThan => match (&left, &right) {
@@ -269,6 +220,8 @@ fn evaluate_relation(
             (Int(l), Int(r)) => Ok(Bool(l == r)),
             (Double(l), Double(r)) => Ok(Bool(l == r)),
             (Decimal(l), Decimal(r)) => Ok(Bool(l == r)),
+            (l, r) if CelType::from(l) == CelType::from(r) => Ok(Bool(l == r)),
+            (Null, _) | (_, Null) => Ok(Bool(left == right)),
             _ => Err(CelError::NoMatchingOverload(format!(
                 "Cannot apply '==' to {:?} and {:?}",
                 CelType::from(&left),
@@ -280,13 +233,31 @@ fn evaluate_relation(
             (Int(l), Int(r)) => Ok(Bool(l != r)),
             (Double(l), Double(r)) => Ok(Bool(l != r)),
             (Decimal(l), Decimal(r)) => Ok(Bool(l != r)),
+            (l, r) if CelType::from(l) == CelType::from(r) => Ok(Bool(l != r)),
+            (Null, _) | (_, Null) => Ok(Bool(left != right)),
             _ => Err(CelError::NoMatchingOverload(format!(
                 "Cannot apply '!=' to {:?} and {:?}",
                 CelType::from(&left),
                 CelType::from(&right)
             ))),
         },
-        _ => unimplemented!(),
+        RelationOp::In => match (&left, &right) {
+            (_, List(list)) => Ok(Bool(list.contains(&left))),
+            (_, Map(map)) => Ok(Bool(map.contains_key(CelKey::try_from(&left)?))),
+            _ => Err(CelError::NoMatchingOverload(format!(
+                "Cannot apply 'in' to {:?} and {:?}",
+                CelType::from(&left),
+                CelType::from(&right)
+            ))),
+        },
+    }
+}
+
+/// Prints the expression in canonical form, independent of the
+/// formatting of the source it was parsed from.
+impl std::fmt::Display for CelExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.expr.fmt(f)
     }
 }
 
@@ -302,10 +273,64 @@ impl TryFrom<String> for CelExpression {
     fn try_from(source: String) -> Result<Self, Self::Error> {
         let expr = ExpressionParser::new()
             .parse(&source)
-            .map_err(|e| CelError::CelParseError(e.to_string()))?;
-        Ok(Self { source, expr })
+            .map_err(|e| parse_error(&source, e))?;
+        if nesting_depth(&expr) > MAX_NESTING_DEPTH {
+            return Err(CelError::BudgetExceeded(format!(
+                "expression is nested deeper than {MAX_NESTING_DEPTH} levels"
+            )));
+        }
+        let plan = Arc::new(Plan::compile(&expr));
+        Ok(Self { source, expr, plan })
+    }
+}
+fn parse_error<T: std::fmt::Display>(source: &str, e: ParseError<usize, T, &str>) -> CelError {
+    let (message, start, end) = match e {
+        ParseError::InvalidToken { location } => {
+            ("invalid token".to_string(), location, location + 1)
+        }
+        ParseError::UnrecognizedEof { location, expected } => (
+            format!("unexpected end of expression{}", expected_tokens(&expected)),
+            location,
+            location,
+        ),
+        ParseError::UnrecognizedToken {
+            token: (start, token, end),
+            expected,
+        } => (
+            format!("unexpected token '{token}'{}", expected_tokens(&expected)),
+            start,
+            end,
+        ),
+        ParseError::ExtraToken {
+            token: (start, token, end),
+        } => (format!("extra token '{token}'"), start, end),
+        ParseError::User { error } => (error.to_string(), 0, source.len()),
+    };
+    CelError::CelParseError(message, CelSourceLocation::new(source, Span { start, end }))
+}
+
+/// Lists the expected tokens, naming the regex based ones by what they match.
+fn expected_tokens(expected: &[String]) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for token in expected {
+        let name = if !token.starts_with("r#") {
+            token.as_str()
+        } else if token.contains("[_a-zA-Z]") {
+            "identifier"
+        } else {
+            "literal"
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        String::new()
+    } else {
+        format!(", expected one of {}", names.join(", "))
     }
 }
+
 impl TryFrom<&str> for CelExpression {
     type Error = CelError;
 
@@ -344,9 +369,8 @@ mod tests {
             CelValue::String("hello".to_string().into())
         );
 
-        // Tokenizer needs fixing
-        // let expression = "1u".parse::<CelExpression>().unwrap();
-        // assert_eq!(expression.evaluate(&context).unwrap(), CelValue::UInt(1))
+        let expression = "1u".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::UInt(1));
     }
 
     #[test]
@@ -384,4 +408,543 @@ mod tests {
             CelValue::Date(NaiveDate::parse_from_str("2022-10-10", "%Y-%m-%d").unwrap())
         );
     }
+
+    #[test]
+    fn lists() {
+        let expression = "[1, 2, 3]".parse::<CelExpression>().unwrap();
+        let context = CelContext::new();
+        let mut list = CelArray::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::from(list));
+
+        let expression = "[1, 2, 3][1]".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Int(2));
+
+        let expression = "[1, 2, 3][3]".parse::<CelExpression>().unwrap();
+        assert!(expression.evaluate(&context).is_err());
+
+        let out_of_bounds =
+            |source: &str| match source.parse::<CelExpression>().unwrap().evaluate(&context) {
+                Err(CelError::EvaluationError(_, e)) => match *e {
+                    CelError::IndexOutOfBounds(i, len) => (i, len),
+                    e => panic!("expected IndexOutOfBounds, got {e:?}"),
+                },
+                res => panic!("expected an error, got {res:?}"),
+            };
+        assert_eq!(out_of_bounds("[1, 2][-1]"), ("-1".to_string(), 2));
+        assert_eq!(
+            out_of_bounds("[1, 2][18446744073709551615u]"),
+            ("18446744073709551615".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        let expression = "params.rates.map(r, fx(r, params.currency)) + [round(1.5m)]"
+            .parse::<CelExpression>()
+            .unwrap();
+        let mut identifiers: Vec<_> = expression.identifiers().collect();
+        identifiers.sort();
+        assert_eq!(identifiers, vec!["fx", "params", "round"]);
+    }
+
+    #[test]
+    fn index() {
+        let mut context = CelContext::new();
+        let mut params = CelMap::new();
+        let mut accounts = CelArray::new();
+        accounts.push("first");
+        accounts.push("second");
+        params.insert("accounts", accounts);
+        let mut fees = CelMap::new();
+        fees.insert("USD", 1);
+        params.insert("fees", fees);
+        context.add_variable("params", params);
+
+        let expression = "params.accounts[0]".parse::<CelExpression>().unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::from("first")
+        );
+
+        let expression = "params.fees['USD']".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Int(1));
+    }
+
+    #[test]
+    fn in_operator() {
+        let mut context = CelContext::new();
+        let mut params = CelMap::new();
+        params.insert("currency", "USD");
+        context.add_variable("params", params);
+
+        let expression = "params.currency in ['USD', 'EUR']"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+
+        let expression = "params.currency in ['BTC']"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Bool(false)
+        );
+
+        let expression = "'currency' in params".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+    }
+
+    #[test]
+    fn has_macro() {
+        let mut context = CelContext::new();
+        let mut params = CelMap::new();
+        params.insert("fee", 1);
+        context.add_variable("params", params);
+
+        let expression = "has(params.fee)".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+
+        let expression = "has(params.tip)".parse::<CelExpression>().unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Bool(false)
+        );
+
+        let expression = "has(params)".parse::<CelExpression>().unwrap();
+        assert!(expression.evaluate(&context).is_err());
+    }
+
+    #[test]
+    fn size() {
+        let context = CelContext::new();
+        let expression = "size([1, 2, 3])".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Int(3));
+
+        let expression = "'hello'.size()".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Int(5));
+
+        let expression = "size({'a': 1})".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Int(1));
+    }
+
+    #[test]
+    fn comprehensions() {
+        let context = CelContext::new();
+        let expression = "[1, 2, 3].all(x, x > 0)".parse::<CelExpression>().unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+
+        let expression = "[1, 2, 3].exists(x, x > 2)"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+
+        let expression = "[1, 2, 3].exists_one(x, x > 1)"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Bool(false)
+        );
+
+        let expression = "[1, 2, 3].filter(x, x > 1)"
+            .parse::<CelExpression>()
+            .unwrap();
+        let mut list = CelArray::new();
+        list.push(2);
+        list.push(3);
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::from(list));
+
+        let expression = "[1, 2, 3].map(x, x > 1, x * 2)"
+            .parse::<CelExpression>()
+            .unwrap();
+        let mut list = CelArray::new();
+        list.push(4);
+        list.push(6);
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::from(list));
+
+        let expression = "{'a': 1}.all(k, k == 'a')"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+    }
+
+    #[test]
+    fn comprehension_scoping() {
+        let mut context = CelContext::new();
+        context.add_variable("x", 10);
+        let expression = "[1, 2].map(x, x + 1).exists(y, y == x)"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Bool(false)
+        );
+
+        let expression = "[[1, 2], [3]].map(l, l.map(x, x * 2))[1][0]"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Int(6));
+    }
+
+    #[test]
+    fn sum_list_params() {
+        use rust_decimal::Decimal;
+
+        let mut context = CelContext::new();
+        let mut params = CelMap::new();
+        let mut items = CelArray::new();
+        for amount in ["1.50", "2.25"] {
+            let mut item = CelMap::new();
+            item.insert("amount", amount.parse::<Decimal>().unwrap());
+            items.push(item);
+        }
+        params.insert("items", items);
+        context.add_variable("params", params);
+
+        let expression = "sum(params.items.map(i, i.amount))"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Decimal("3.75".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn custom_function() {
+        let mut context = CelContext::new();
+        context.add_function("fx_rate", |args| match (args.first(), args.get(1)) {
+            (Some(CelValue::String(from)), Some(CelValue::String(to)))
+                if from.as_str() == "USD" && to.as_str() == "EUR" =>
+            {
+                Ok(CelValue::Decimal("0.9".parse().unwrap()))
+            }
+            _ => Err(CelError::MissingArgument),
+        });
+        let expression = "fx_rate('USD', 'EUR')".parse::<CelExpression>().unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Decimal("0.9".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let mut context = CelContext::new();
+        context.add_variable("order", "6b7a3f1e-4a3e-4c0e-9d4b-3e4f7a0b1c2d");
+        let expression = "Metadata{order_id: order, channel: 'web'}"
+            .parse::<CelExpression>()
+            .unwrap();
+        let CelValue::Map(map) = expression.evaluate(&context).unwrap() else {
+            panic!("expected a map")
+        };
+        assert_eq!(map.type_name(), Some("Metadata"));
+        assert_eq!(map.get("channel"), CelValue::from("web"));
+
+        context.add_message_type(
+            "Metadata",
+            vec![
+                ("order_id".to_string(), CelType::String),
+                ("channel".to_string(), CelType::String),
+            ],
+        );
+        assert!(expression.evaluate(&context).is_ok());
+        let evaluation_error =
+            |source: &str| match source.parse::<CelExpression>().unwrap().evaluate(&context) {
+                Err(CelError::EvaluationError(_, e)) => *e,
+                res => panic!("expected an error, got {res:?}"),
+            };
+        assert!(matches!(
+            evaluation_error("Metadata{order: order}"),
+            CelError::UnknownField(_)
+        ));
+        assert!(matches!(
+            evaluation_error("Metadata{channel: 1}"),
+            CelError::BadType(CelType::String, CelType::Int)
+        ));
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let err = "params.amount +\n  * 2"
+            .parse::<CelExpression>()
+            .unwrap_err();
+        let CelError::CelParseError(message, location) = &err else {
+            panic!("expected parse error, got {err:?}")
+        };
+        assert!(message.starts_with("unexpected token '*'"));
+        assert_eq!((location.line, location.column), (2, 3));
+        assert_eq!(location.snippet, "*");
+        assert!(err.to_string().ends_with("    * 2\n    ^"));
+    }
+
+    #[test]
+    fn evaluation_error_location() {
+        let expression = "1 + params.missing.value".parse::<CelExpression>().unwrap();
+        let mut context = CelContext::new();
+        context.add_variable("params", CelMap::new());
+        let err = expression.evaluate(&context).unwrap_err();
+        let CelError::EvaluationError(location, inner) = &err else {
+            panic!("expected evaluation error, got {err:?}")
+        };
+        assert!(matches!(inner.as_ref(), CelError::IllegalTarget));
+        assert_eq!((location.line, location.column), (1, 5));
+        assert_eq!(location.snippet, "params.missing.value");
+        assert!(err
+            .to_string()
+            .ends_with("1 + params.missing.value\n      ^^^^^^^^^^^^^^^^^^^^"));
+    }
+
+    #[test]
+    fn decimal_literals_and_promotion() {
+        let mut params = CelMap::new();
+        params.insert("amount", CelValue::Decimal("10.25".parse().unwrap()));
+        let mut context = CelContext::new();
+        context.add_variable("params", params);
+
+        let expression = "params.amount * 2".parse::<CelExpression>().unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Decimal("20.50".parse().unwrap())
+        );
+
+        let expression = "1 + 1.5m".parse::<CelExpression>().unwrap();
+        assert_eq!(
+            expression.evaluate(&context).unwrap(),
+            CelValue::Decimal("2.5".parse().unwrap())
+        );
+
+        let expression = "params.amount > 10 && params.amount == 10.25m"
+            .parse::<CelExpression>()
+            .unwrap();
+        assert_eq!(expression.evaluate(&context).unwrap(), CelValue::Bool(true));
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        let context = CelContext::new();
+        let eval = |source: &str| source.parse::<CelExpression>().unwrap().evaluate(&context);
+        let inner = |source: &str| match eval(source) {
+            Err(CelError::EvaluationError(_, e)) => *e,
+            res => panic!("expected an error, got {res:?}"),
+        };
+
+        assert!(matches!(
+            inner("9223372036854775807 + 1"),
+            CelError::Overflow(_)
+        ));
+        assert!(matches!(inner("0u - 1u"), CelError::Overflow(_)));
+        assert!(matches!(
+            inner("-9223372036854775808 / -1"),
+            CelError::Overflow(_)
+        ));
+        assert!(matches!(
+            inner("79228162514264337593543950335m * 2"),
+            CelError::Overflow(_)
+        ));
+        assert!(matches!(inner("1 % 0"), CelError::DivisionByZero));
+        assert!(matches!(inner("1.5m / 0"), CelError::DivisionByZero));
+        assert_eq!(eval("7 / 2").unwrap(), CelValue::Int(3));
+        assert_eq!(eval("-7 % 3").unwrap(), CelValue::Int(-1));
+        assert_eq!(
+            eval("1m / 4").unwrap(),
+            CelValue::Decimal("0.25".parse().unwrap())
+        );
+        assert_eq!(eval("-(1 + 2)").unwrap(), CelValue::Int(-3));
+        assert_eq!(eval("!(1 > 2)").unwrap(), CelValue::Bool(true));
+    }
+
+    fn arb_source() -> impl proptest::strategy::Strategy<Value = String> {
+        use proptest::prelude::*;
+
+        let leaf = prop_oneof![
+            any::<i64>().prop_map(|i| i.to_string()),
+            any::<u64>().prop_map(|u| format!("{u}u")),
+            (any::<i64>(), 0..30u32).prop_map(|(i, scale)| {
+                let digits = i.unsigned_abs().to_string();
+                let scale = (scale as usize).min(digits.len());
+                let (int, frac) = digits.split_at(digits.len() - scale);
+                let sign = if i < 0 { "-" } else { "" };
+                match (int, frac) {
+                    ("", frac) => format!("{sign}0.{frac}m"),
+                    (int, "") => format!("{sign}{int}m"),
+                    (int, frac) => format!("{sign}{int}.{frac}m"),
+                }
+            }),
+            any::<f64>().prop_map(|d| format!("{d:?}")),
+            Just("'text'".to_string()),
+            Just("''".to_string()),
+            Just("b'bytes'".to_string()),
+            Just("true".to_string()),
+            Just("null".to_string()),
+            Just("params.amount".to_string()),
+            Just("params.count".to_string()),
+            Just("params.list".to_string()),
+            Just("params.name".to_string()),
+            Just("params.missing".to_string()),
+        ];
+        let functions = prop_oneof![
+            Just("size"),
+            Just("sum"),
+            Just("round"),
+            Just("floor"),
+            Just("ceil"),
+            Just("abs"),
+            Just("min"),
+            Just("max"),
+            Just("percent"),
+            Just("string"),
+            Just("int"),
+            Just("uint"),
+            Just("double"),
+            Just("decimal"),
+            Just("date"),
+            Just("uuid"),
+            Just("matches"),
+        ];
+        let methods = prop_oneof![
+            Just("startsWith"),
+            Just("contains"),
+            Just("lowerAscii"),
+            Just("size"),
+            Just("all"),
+            Just("exists"),
+            Just("map"),
+            Just("filter"),
+        ];
+        let ops = prop_oneof![
+            Just("+"),
+            Just("-"),
+            Just("*"),
+            Just("/"),
+            Just("%"),
+            Just("=="),
+            Just("!="),
+            Just("<"),
+            Just(">="),
+            Just("in"),
+            Just("&&"),
+            Just("||"),
+        ];
+        leaf.prop_recursive(4, 32, 3, move |inner| {
+            prop_oneof![
+                (inner.clone(), ops.clone(), inner.clone())
+                    .prop_map(|(l, op, r)| format!("({l}) {op} ({r})")),
+                (prop_oneof![Just("-"), Just("!")], inner.clone())
+                    .prop_map(|(op, e)| format!("{op}({e})")),
+                (inner.clone(), inner.clone(), inner.clone())
+                    .prop_map(|(c, l, r)| format!("({c}) ? ({l}) : ({r})")),
+                (
+                    functions.clone(),
+                    prop::collection::vec(inner.clone(), 0..3)
+                )
+                    .prop_map(|(f, args)| format!("{f}({})", args.join(", "))),
+                (inner.clone(), methods.clone(), inner.clone())
+                    .prop_map(|(t, m, e)| format!("({t}).{m}(x, {e})")),
+                (inner.clone(), methods.clone(), inner.clone())
+                    .prop_map(|(t, m, e)| format!("({t}).{m}({e})")),
+                prop::collection::vec(inner.clone(), 0..3)
+                    .prop_map(|items| format!("[{}]", items.join(", "))),
+                (inner.clone(), inner.clone()).prop_map(|(t, i)| format!("({t})[{i}]")),
+                (inner.clone(), inner).prop_map(|(k, v)| format!("{{{k}: {v}}}")),
+            ]
+        })
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn evaluation_never_panics(source in arb_source()) {
+            let mut params = CelMap::new();
+            params.insert("amount", CelValue::Decimal(rust_decimal::Decimal::MAX));
+            params.insert("count", CelValue::Int(i64::MIN));
+            params.insert(
+                "list",
+                CelValue::from(CelArray::from(vec![CelValue::Int(1), CelValue::UInt(u64::MAX)])),
+            );
+            params.insert("name", "payout");
+            let mut context = CelContext::new();
+            context.add_variable("params", params);
+
+            if let Ok(expression) = source.parse::<CelExpression>() {
+                // Template fields such as metadata are converted to JSON after evaluation
+                let _ = expression.try_evaluate::<serde_json::Value, _>(&context);
+            }
+        }
+
+        #[test]
+        fn printed_expressions_round_trip(source in arb_source()) {
+            if let Ok(expression) = source.parse::<CelExpression>() {
+                let printed = expression.to_string();
+                let reparsed = printed.parse::<CelExpression>().unwrap();
+                proptest::prop_assert_eq!(&reparsed.expr, &expression.expr, "{}", printed);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_deeply_nested_expressions() {
+        let source = format!("{}1{}", "[".repeat(5_000), "]".repeat(5_000));
+        assert!(matches!(
+            source.parse::<CelExpression>(),
+            Err(CelError::BudgetExceeded(_))
+        ));
+        let source = format!("{}1{}", "[".repeat(50), "]".repeat(50));
+        assert!(source.parse::<CelExpression>().is_ok());
+    }
+
+    #[test]
+    fn evaluation_budget() {
+        let mut context = CelContext::new();
+        context.add_variable("one", 1);
+        let exceeds = |source: &str, budget: CelBudget| {
+            let err = source
+                .parse::<CelExpression>()
+                .unwrap()
+                .evaluate_with_budget(&context, &budget)
+                .unwrap_err();
+            matches!(err, CelError::EvaluationError(_, e) if matches!(*e, CelError::BudgetExceeded(_)))
+        };
+
+        assert!(exceeds(
+            "[1, 2, 3].map(x, [1, 2, 3].map(y, x * y))",
+            CelBudget {
+                max_node_visits: 20,
+                ..Default::default()
+            }
+        ));
+        assert!(exceeds(
+            "((one + 2) + 3) + 4",
+            CelBudget {
+                max_depth: 2,
+                ..Default::default()
+            }
+        ));
+        assert!(exceeds(
+            "[1, 2] + [3]",
+            CelBudget {
+                max_list_size: 2,
+                ..Default::default()
+            }
+        ));
+        assert!(exceeds(
+            "'abc' + 'def'",
+            CelBudget {
+                max_string_size: 5,
+                ..Default::default()
+            }
+        ));
+        assert_eq!(
+            "'abc' + 'def'"
+                .parse::<CelExpression>()
+                .unwrap()
+                .evaluate(&context)
+                .unwrap(),
+            CelValue::String("abcdef".to_string().into())
+        );
+    }
 }
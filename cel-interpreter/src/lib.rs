@@ -1,14 +1,22 @@
 #![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
 #![cfg_attr(feature = "fail-on-warnings", deny(clippy::all))]
 
+mod budget;
 mod builtins;
 mod cel_type;
+mod checker;
+mod clock;
 mod context;
 mod error;
 mod interpreter;
+mod plan;
 mod value;
 
+pub use budget::{CelBudget, MAX_NESTING_DEPTH};
 pub use cel_type::*;
+pub use checker::*;
+pub use chrono_tz::Tz;
+pub use clock::*;
 pub use context::*;
 pub use error::*;
 pub use interpreter::*;
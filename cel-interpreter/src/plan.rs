@@ -0,0 +1,742 @@
+use cel_parser::ast::{self, ArithmeticOp, Expression, RelationOp, Span, UnaryOp};
+
+use std::sync::{Arc, OnceLock};
+
+use crate::{
+    budget::*,
+    cel_type::*,
+    context::*,
+    error::*,
+    interpreter::{evaluate_arithmetic, evaluate_relation, evaluate_unary},
+    value::*,
+};
+
+type NodeId = usize;
+
+/// Builtins that always return the same result for the same arguments
+/// and may therefore be evaluated at compile time when all arguments are constant.
+/// Contexts that register a function or variable of the same name are evaluated
+/// against a plan without folded calls.
+pub(crate) const PURE_FUNCTIONS: [&str; 22] = [
+    "uuid",
+    "decimal",
+    "date",
+    "size",
+    "sum",
+    "round",
+    "floor",
+    "ceil",
+    "abs",
+    "min",
+    "max",
+    "percent",
+    "string",
+    "int",
+    "uint",
+    "double",
+    "startsWith",
+    "endsWith",
+    "contains",
+    "matches",
+    "lowerAscii",
+    "upperAscii",
+];
+
+/// An expression compiled into a flat list of nodes.
+///
+/// Constant sub-expressions are folded into values and identifiers
+/// are resolved against the context once per evaluation instead of once per use.
+#[derive(Debug)]
+pub(crate) struct Plan {
+    nodes: Vec<Node>,
+    root: NodeId,
+    globals: Vec<Arc<String>>,
+    /// Builtins whose calls were evaluated at compile time.
+    folded_calls: Vec<Arc<String>>,
+    /// The expression compiled without folding calls, for contexts
+    /// that shadow one of the folded builtins.
+    unfolded: Option<Box<Plan>>,
+}
+
+#[derive(Debug)]
+struct Node {
+    span: Option<Span>,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    Const(CelValue),
+    /// Index into [Plan::globals].
+    Global(usize),
+    /// Index of a comprehension variable, counting from the outermost comprehension.
+    Local(usize),
+    Ternary(NodeId, NodeId, NodeId),
+    Arithmetic(ArithmeticOp, NodeId, NodeId),
+    Relation(RelationOp, NodeId, NodeId),
+    Unary(UnaryOp, NodeId),
+    Attribute(NodeId, CelKey),
+    Index(NodeId, NodeId),
+    Has(NodeId, CelKey),
+    /// Call of the global function in the given slot.
+    Call(usize, Vec<NodeId>),
+    List(Vec<NodeId>),
+    Map(Vec<(NodeId, NodeId)>),
+    /// Construction of a message of the named type.
+    Struct(Arc<String>, Vec<(Arc<String>, NodeId)>),
+    Comprehension {
+        kind: Comprehension,
+        range: NodeId,
+        filter: Option<NodeId>,
+        transform: Option<NodeId>,
+    },
+    /// An expression that is known to fail when evaluated.
+    Invalid(Invalid),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comprehension {
+    All,
+    Exists,
+    ExistsOne,
+    Map,
+    Filter,
+}
+
+#[derive(Debug)]
+enum Invalid {
+    Macro(String),
+    IllegalTarget,
+    /// A literal that can't be represented as a value.
+    Literal(ast::Literal),
+}
+
+impl Invalid {
+    fn to_error(&self) -> CelError {
+        match self {
+            Invalid::Macro(msg) => CelError::InvalidMacro(msg.clone()),
+            Invalid::IllegalTarget => CelError::IllegalTarget,
+            Invalid::Literal(literal) => match CelValue::try_from(literal) {
+                Err(e) => e,
+                Ok(_) => CelError::Unexpected(format!("literal {literal} is valid")),
+            },
+        }
+    }
+}
+
+/// An error raised during evaluation together with the span
+/// of the innermost expression that produced it.
+pub(crate) struct EvalError {
+    pub(crate) span: Option<Span>,
+    pub(crate) error: CelError,
+}
+
+impl From<CelError> for EvalError {
+    fn from(error: CelError) -> Self {
+        Self { span: None, error }
+    }
+}
+
+enum EvalType<'a> {
+    Value(CelValue),
+    ContextItem(&'a ContextItem),
+}
+
+impl<'a> EvalType<'a> {
+    fn try_bool(&self) -> Result<bool, CelError> {
+        self.as_value()?.try_bool()
+    }
+
+    fn try_key(&self) -> Result<CelKey, CelError> {
+        CelKey::try_from(self.as_value()?)
+    }
+
+    fn try_value(self) -> Result<CelValue, CelError> {
+        match self {
+            EvalType::Value(val) => Ok(val),
+            EvalType::ContextItem(ContextItem::Value(val)) => Ok(val.clone()),
+            _ => Err(CelError::Unexpected("Couldn't unwrap value".to_string())),
+        }
+    }
+
+    fn as_value(&self) -> Result<&CelValue, CelError> {
+        match self {
+            EvalType::Value(val) => Ok(val),
+            EvalType::ContextItem(ContextItem::Value(val)) => Ok(val),
+            _ => Err(CelError::IllegalTarget),
+        }
+    }
+}
+
+impl Plan {
+    pub(crate) fn compile(expr: &Expression) -> Self {
+        let mut plan = Compiler::new(true).finish(expr);
+        if !plan.folded_calls.is_empty() {
+            plan.unfolded = Some(Box::new(Compiler::new(false).finish(expr)));
+        }
+        plan
+    }
+
+    /// Names of the identifiers resolved against the context.
+    pub(crate) fn globals(&self) -> impl Iterator<Item = &str> {
+        self.globals.iter().map(|name| name.as_str())
+    }
+
+    pub(crate) fn evaluate(&self, ctx: &CelContext, meter: &Meter) -> Result<CelValue, EvalError> {
+        let plan = match &self.unfolded {
+            Some(unfolded) if self.folded_calls.iter().any(|name| ctx.shadows(name)) => unfolded,
+            _ => self,
+        };
+        let mut runtime = Runtime::new(&plan.nodes, &plan.globals, ctx, meter);
+        Ok(runtime.eval(plan.root)?.try_value()?)
+    }
+}
+
+/// Context used to fold calls to pure builtins.
+fn builtins() -> &'static CelContext {
+    static BUILTINS: OnceLock<CelContext> = OnceLock::new();
+    BUILTINS.get_or_init(CelContext::new)
+}
+
+struct Compiler {
+    nodes: Vec<Node>,
+    globals: Vec<Arc<String>>,
+    /// Comprehension variables in scope, innermost last.
+    scope: Vec<Arc<String>>,
+    fold_calls: bool,
+    folded_calls: Vec<Arc<String>>,
+}
+
+impl Compiler {
+    fn new(fold_calls: bool) -> Self {
+        Self {
+            nodes: Vec::new(),
+            globals: Vec::new(),
+            scope: Vec::new(),
+            fold_calls,
+            folded_calls: Vec::new(),
+        }
+    }
+
+    fn finish(mut self, expr: &Expression) -> Plan {
+        let root = self.compile(expr, None);
+        Plan {
+            nodes: self.nodes,
+            root,
+            globals: self.globals,
+            folded_calls: self.folded_calls,
+            unfolded: None,
+        }
+    }
+
+    fn compile(&mut self, expr: &Expression, span: Option<Span>) -> NodeId {
+        use Expression::*;
+        let kind = match expr {
+            Spanned(span, inner) => return self.compile(inner, Some(*span)),
+            Ternary(cond, left, right) => {
+                let cond = self.compile(cond, None);
+                let left = self.compile(left, None);
+                let right = self.compile(right, None);
+                if let NodeKind::Const(CelValue::Bool(c)) = self.nodes[cond].kind {
+                    return if c { left } else { right };
+                }
+                NodeKind::Ternary(cond, left, right)
+            }
+            Relation(op, left, right) => {
+                NodeKind::Relation(*op, self.compile(left, None), self.compile(right, None))
+            }
+            Arithmetic(op, left, right) => {
+                NodeKind::Arithmetic(*op, self.compile(left, None), self.compile(right, None))
+            }
+            Unary(op, expr) => NodeKind::Unary(op.clone(), self.compile(expr, None)),
+            Member(target, member) => self.compile_member(target, member),
+            List(exprs) => NodeKind::List(exprs.iter().map(|e| self.compile(e, None)).collect()),
+            Map(entries) => NodeKind::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (self.compile(k, None), self.compile(v, None)))
+                    .collect(),
+            ),
+            Struct(names, fields) => NodeKind::Struct(
+                Arc::new(
+                    names
+                        .iter()
+                        .map(|name| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                ),
+                fields
+                    .iter()
+                    .map(|(name, e)| (Arc::clone(name), self.compile(e, None)))
+                    .collect(),
+            ),
+            Literal(literal) => match CelValue::try_from(literal) {
+                Ok(val) => NodeKind::Const(val),
+                Err(_) => NodeKind::Invalid(Invalid::Literal(literal.clone())),
+            },
+            Ident(name) => match self.scope.iter().rposition(|var| var == name) {
+                Some(idx) => NodeKind::Local(idx),
+                None => NodeKind::Global(self.global(name)),
+            },
+        };
+        self.push(span, kind)
+    }
+
+    fn compile_member(&mut self, target: &Expression, member: &ast::Member) -> NodeKind {
+        use ast::Member::*;
+        match (target.unspanned(), member) {
+            (Expression::Ident(name), FunctionCall(args)) if name.as_str() == "has" => {
+                match args
+                    .iter()
+                    .map(Expression::unspanned)
+                    .collect::<Vec<_>>()
+                    .as_slice()
+                {
+                    [Expression::Member(target, attr)] => match attr.as_ref() {
+                        Attribute(field) => {
+                            NodeKind::Has(self.compile(target, None), CelKey::from(field))
+                        }
+                        _ => has_error(),
+                    },
+                    _ => has_error(),
+                }
+            }
+            (Expression::Ident(name), FunctionCall(args)) => {
+                let slot = self.global(name);
+                NodeKind::Call(slot, args.iter().map(|e| self.compile(e, None)).collect())
+            }
+            (Expression::Member(receiver, attr), FunctionCall(args)) => match attr.as_ref() {
+                Attribute(name) => self.compile_method(receiver, name, args),
+                _ => NodeKind::Invalid(Invalid::IllegalTarget),
+            },
+            (_, FunctionCall(_)) => NodeKind::Invalid(Invalid::IllegalTarget),
+            (_, Attribute(name)) => {
+                NodeKind::Attribute(self.compile(target, None), CelKey::from(name))
+            }
+            (_, Index(idx)) => NodeKind::Index(self.compile(target, None), self.compile(idx, None)),
+        }
+    }
+
+    fn compile_method(
+        &mut self,
+        receiver: &Expression,
+        name: &Arc<String>,
+        args: &[Expression],
+    ) -> NodeKind {
+        let kind = match name.as_str() {
+            "all" => Comprehension::All,
+            "exists" => Comprehension::Exists,
+            "exists_one" => Comprehension::ExistsOne,
+            "map" => Comprehension::Map,
+            "filter" => Comprehension::Filter,
+            _ => {
+                let slot = self.global(name);
+                let mut nodes = vec![self.compile(receiver, None)];
+                nodes.extend(args.iter().map(|e| self.compile(e, None)));
+                return NodeKind::Call(slot, nodes);
+            }
+        };
+        let invalid = || {
+            NodeKind::Invalid(Invalid::Macro(format!(
+                "Unsupported arguments for '{name}'"
+            )))
+        };
+        let (var, rest) = match args.split_first() {
+            Some((var, rest)) => match var.unspanned() {
+                Expression::Ident(var) => (var, rest),
+                _ => return invalid(),
+            },
+            None => return invalid(),
+        };
+        let (filter, transform) = match (kind, rest) {
+            (Comprehension::Map, [transform]) => (None, Some(transform)),
+            (Comprehension::Map, [filter, transform]) => (Some(filter), Some(transform)),
+            (Comprehension::Map, _) => return invalid(),
+            (_, [predicate]) => (Some(predicate), None),
+            _ => return invalid(),
+        };
+
+        let range = self.compile(receiver, None);
+        self.scope.push(Arc::clone(var));
+        let filter = filter.map(|e| self.compile(e, None));
+        let transform = transform.map(|e| self.compile(e, None));
+        self.scope.pop();
+        NodeKind::Comprehension {
+            kind,
+            range,
+            filter,
+            transform,
+        }
+    }
+
+    fn global(&mut self, name: &Arc<String>) -> usize {
+        match self.globals.iter().position(|g| g == name) {
+            Some(slot) => slot,
+            None => {
+                self.globals.push(Arc::clone(name));
+                self.globals.len() - 1
+            }
+        }
+    }
+
+    /// Adds a node, replacing it by its value if it can be computed up front.
+    fn push(&mut self, span: Option<Span>, kind: NodeKind) -> NodeId {
+        let foldable = match &kind {
+            NodeKind::Ternary(..)
+            | NodeKind::Arithmetic(..)
+            | NodeKind::Relation(..)
+            | NodeKind::Unary(..)
+            | NodeKind::Attribute(..)
+            | NodeKind::Index(..)
+            | NodeKind::Has(..)
+            | NodeKind::List(..)
+            | NodeKind::Map(..) => true,
+            NodeKind::Call(slot, args) => {
+                self.fold_calls
+                    && PURE_FUNCTIONS.contains(&self.globals[*slot].as_str())
+                    && !(self.globals[*slot].as_str() == "date" && args.is_empty())
+            }
+            _ => false,
+        } && children(&kind)
+            .iter()
+            .all(|id| matches!(self.nodes[*id].kind, NodeKind::Const(_)));
+
+        self.nodes.push(Node { span, kind });
+        let id = self.nodes.len() - 1;
+        if foldable {
+            let budget = CelBudget::default();
+            let meter = Meter::new(&budget);
+            let mut runtime = Runtime::new(&self.nodes, &self.globals, builtins(), &meter);
+            // Expressions that fail are left in place to report the error when evaluated
+            if let Ok(val) = runtime.eval(id).and_then(|v| Ok(v.try_value()?)) {
+                if let NodeKind::Call(slot, _) = self.nodes[id].kind {
+                    let name = &self.globals[slot];
+                    if !self.folded_calls.contains(name) {
+                        self.folded_calls.push(Arc::clone(name));
+                    }
+                }
+                self.nodes[id].kind = NodeKind::Const(val);
+            }
+        }
+        id
+    }
+}
+
+fn has_error() -> NodeKind {
+    NodeKind::Invalid(Invalid::Macro(
+        "has() requires a field selection".to_string(),
+    ))
+}
+
+fn children(kind: &NodeKind) -> Vec<NodeId> {
+    match kind {
+        NodeKind::Ternary(c, l, r) => vec![*c, *l, *r],
+        NodeKind::Arithmetic(_, l, r) | NodeKind::Relation(_, l, r) | NodeKind::Index(l, r) => {
+            vec![*l, *r]
+        }
+        NodeKind::Unary(_, e) | NodeKind::Attribute(e, _) | NodeKind::Has(e, _) => vec![*e],
+        NodeKind::Call(_, args) | NodeKind::List(args) => args.clone(),
+        NodeKind::Map(entries) => entries.iter().flat_map(|(k, v)| [*k, *v]).collect(),
+        NodeKind::Struct(_, fields) => fields.iter().map(|(_, v)| *v).collect(),
+        NodeKind::Comprehension {
+            range,
+            filter,
+            transform,
+            ..
+        } => [Some(*range), *filter, *transform]
+            .into_iter()
+            .flatten()
+            .collect(),
+        NodeKind::Const(_) | NodeKind::Global(_) | NodeKind::Local(_) | NodeKind::Invalid(_) => {
+            Vec::new()
+        }
+    }
+}
+
+struct Runtime<'p, 'c, 'm, 'b> {
+    ctx: &'c CelContext,
+    nodes: &'p [Node],
+    names: &'p [Arc<String>],
+    globals: Vec<Option<&'c ContextItem>>,
+    locals: Vec<CelValue>,
+    meter: &'m Meter<'b>,
+}
+
+impl<'p, 'c, 'm, 'b> Runtime<'p, 'c, 'm, 'b> {
+    fn new(
+        nodes: &'p [Node],
+        names: &'p [Arc<String>],
+        ctx: &'c CelContext,
+        meter: &'m Meter<'b>,
+    ) -> Self {
+        Self {
+            nodes,
+            names,
+            ctx,
+            globals: names.iter().map(|name| ctx.get(name)).collect(),
+            locals: Vec::new(),
+            meter,
+        }
+    }
+
+    fn eval(&mut self, id: NodeId) -> Result<EvalType<'c>, EvalError> {
+        let nodes = self.nodes;
+        let node = &nodes[id];
+        let meter = self.meter;
+        let _depth = meter.enter()?;
+        let res = self.eval_node(&node.kind).map_err(|mut e| {
+            if e.span.is_none() {
+                e.span = node.span;
+            }
+            e
+        })?;
+        if let EvalType::Value(val) = &res {
+            meter.check_size(val).map_err(|error| EvalError {
+                span: node.span,
+                error,
+            })?;
+        }
+        Ok(res)
+    }
+
+    fn value(&mut self, id: NodeId) -> Result<CelValue, EvalError> {
+        Ok(self.eval(id)?.try_value()?)
+    }
+
+    fn global(&self, slot: usize) -> Result<&'c ContextItem, CelError> {
+        self.globals[slot].ok_or_else(|| CelError::UnknownIdent(self.names[slot].to_string()))
+    }
+
+    fn eval_node(&mut self, kind: &NodeKind) -> Result<EvalType<'c>, EvalError> {
+        use NodeKind::*;
+        match kind {
+            Const(val) => Ok(EvalType::Value(val.clone())),
+            Global(slot) => Ok(EvalType::ContextItem(self.global(*slot)?)),
+            Local(idx) => Ok(EvalType::Value(self.locals[*idx].clone())),
+            Ternary(cond, left, right) => {
+                if self.eval(*cond)?.try_bool()? {
+                    self.eval(*left)
+                } else {
+                    self.eval(*right)
+                }
+            }
+            Arithmetic(op, left, right) => {
+                let left = self.value(*left)?;
+                let right = self.value(*right)?;
+                Ok(EvalType::Value(evaluate_arithmetic(*op, left, right)?))
+            }
+            Relation(op, left, right) => {
+                let left = self.value(*left)?;
+                let right = self.value(*right)?;
+                Ok(EvalType::Value(evaluate_relation(*op, left, right)?))
+            }
+            Unary(op, expr) => {
+                let val = self.value(*expr)?;
+                Ok(EvalType::Value(evaluate_unary(op, val)?))
+            }
+            Attribute(target, key) => match self.eval(*target)?.as_value()? {
+                CelValue::Map(map) => Ok(EvalType::Value(map.get(key.clone()))),
+                _ => Err(CelError::IllegalTarget.into()),
+            },
+            Index(target, idx) => {
+                let target = self.eval(*target)?;
+                let idx = self.eval(*idx)?;
+                Ok(EvalType::Value(index(target.as_value()?, &idx)?))
+            }
+            Has(target, key) => match self.eval(*target)?.as_value()? {
+                CelValue::Map(map) => Ok(EvalType::Value(CelValue::Bool(
+                    map.contains_key(key.clone()),
+                ))),
+                v => Err(CelError::BadType(CelType::Map, CelType::from(v)).into()),
+            },
+            Call(slot, args) => match self.global(*slot)? {
+                ContextItem::Function(f) => {
+                    let mut values = Vec::with_capacity(args.len());
+                    for arg in args {
+                        values.push(self.value(*arg)?);
+                    }
+                    Ok(EvalType::Value(f(values)?))
+                }
+                _ => Err(CelError::IllegalTarget.into()),
+            },
+            List(items) => {
+                let mut list = Vec::with_capacity(items.len());
+                for item in items {
+                    list.push(self.value(*item)?);
+                }
+                Ok(EvalType::Value(CelValue::from(CelArray::from(list))))
+            }
+            Map(entries) => {
+                let mut map = CelMap::new();
+                for (k, v) in entries {
+                    let key = self.eval(*k)?.try_key()?;
+                    let value = self.value(*v)?;
+                    map.insert(key, value);
+                }
+                Ok(EvalType::Value(CelValue::from(map)))
+            }
+            Struct(type_name, fields) => {
+                let mut map = CelMap::new_message(type_name.as_str());
+                for (name, v) in fields {
+                    let value = self.value(*v)?;
+                    self.ctx.check_message_field(type_name, name, &value)?;
+                    map.insert(name, value);
+                }
+                Ok(EvalType::Value(CelValue::from(map)))
+            }
+            Comprehension {
+                kind,
+                range,
+                filter,
+                transform,
+            } => Ok(EvalType::Value(
+                self.comprehension(*kind, *range, *filter, *transform)?,
+            )),
+            Invalid(invalid) => Err(invalid.to_error().into()),
+        }
+    }
+
+    fn comprehension(
+        &mut self,
+        kind: Comprehension,
+        range: NodeId,
+        filter: Option<NodeId>,
+        transform: Option<NodeId>,
+    ) -> Result<CelValue, EvalError> {
+        let range: Vec<CelValue> = match self.eval(range)?.as_value()? {
+            CelValue::List(list) => list.iter().cloned().collect(),
+            CelValue::Map(map) => map.keys().map(CelValue::from).collect(),
+            v => return Err(CelError::BadType(CelType::List, CelType::from(v)).into()),
+        };
+
+        let mut matches = 0;
+        let mut results = Vec::new();
+        for elem in range {
+            self.locals.push(elem);
+            let step = self.comprehension_step(filter, transform);
+            let elem = self.locals.pop().expect("comprehension variable in scope");
+            let (keep, mapped) = step?;
+            match kind {
+                Comprehension::All if !keep => return Ok(CelValue::Bool(false)),
+                Comprehension::Exists if keep => return Ok(CelValue::Bool(true)),
+                Comprehension::ExistsOne if keep => matches += 1,
+                Comprehension::Map if keep => {
+                    results.push(mapped.expect("map always has a transform"))
+                }
+                Comprehension::Filter if keep => results.push(elem),
+                _ => (),
+            }
+        }
+
+        Ok(match kind {
+            Comprehension::All => CelValue::Bool(true),
+            Comprehension::Exists => CelValue::Bool(false),
+            Comprehension::ExistsOne => CelValue::Bool(matches == 1),
+            Comprehension::Map | Comprehension::Filter => CelValue::from(CelArray::from(results)),
+        })
+    }
+
+    /// Evaluates the filter and transform of a comprehension for the innermost variable.
+    fn comprehension_step(
+        &mut self,
+        filter: Option<NodeId>,
+        transform: Option<NodeId>,
+    ) -> Result<(bool, Option<CelValue>), EvalError> {
+        let keep = match filter {
+            Some(predicate) => self.eval(predicate)?.try_bool()?,
+            None => true,
+        };
+        let mapped = match transform {
+            Some(transform) if keep => Some(self.value(transform)?),
+            _ => None,
+        };
+        Ok((keep, mapped))
+    }
+}
+
+fn index(target: &CelValue, idx: &EvalType) -> Result<CelValue, CelError> {
+    match target {
+        CelValue::List(list) => {
+            let i = match idx.as_value()? {
+                CelValue::Int(i) => usize::try_from(*i).map_err(|_| i.to_string()),
+                CelValue::UInt(u) => usize::try_from(*u).map_err(|_| u.to_string()),
+                v => return Err(CelError::BadType(CelType::Int, CelType::from(v))),
+            };
+            match i {
+                Ok(i) => list
+                    .get(i)
+                    .cloned()
+                    .ok_or_else(|| CelError::IndexOutOfBounds(i.to_string(), list.len())),
+                Err(i) => Err(CelError::IndexOutOfBounds(i, list.len())),
+            }
+        }
+        CelValue::Map(map) => Ok(map.get(idx.try_key()?)),
+        _ => Err(CelError::IllegalTarget),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Plan {
+        Plan::compile(
+            &cel_parser::parser::ExpressionParser::new()
+                .parse(source)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn folds_constants() {
+        let plan = compile("uuid('6b7a3f1e-4a3e-4c0e-9d4b-3e4f7a0b1c2d')");
+        assert!(matches!(
+            plan.nodes[plan.root].kind,
+            NodeKind::Const(CelValue::Uuid(_))
+        ));
+        let plan = compile("true ? decimal('1.5') * 2 : params.amount");
+        assert!(matches!(
+            plan.nodes[plan.root].kind,
+            NodeKind::Const(CelValue::Decimal(_))
+        ));
+        let plan = compile("date()");
+        assert!(matches!(plan.nodes[plan.root].kind, NodeKind::Call(..)));
+        let plan = compile("1 / 0");
+        assert!(matches!(
+            plan.nodes[plan.root].kind,
+            NodeKind::Arithmetic(..)
+        ));
+    }
+
+    #[test]
+    fn shadowed_builtins_are_not_folded() {
+        let plan = compile("round(1.5m) + 1");
+        assert!(matches!(
+            plan.nodes[plan.root].kind,
+            NodeKind::Const(CelValue::Decimal(_))
+        ));
+        let budget = CelBudget::default();
+        let mut ctx = CelContext::new();
+        ctx.add_function("round", |_| Ok(CelValue::Int(7)));
+        assert_eq!(
+            plan.evaluate(&ctx, &Meter::new(&budget)).ok(),
+            Some(CelValue::Int(8))
+        );
+        let mut ctx = CelContext::new();
+        ctx.add_function("fx_rate", |_| Ok(CelValue::Int(7)));
+        assert_eq!(
+            plan.evaluate(&ctx, &Meter::new(&budget)).ok(),
+            Some(CelValue::Decimal(3.into()))
+        );
+    }
+
+    #[test]
+    fn resolves_identifier_slots() {
+        let plan = compile("params.a + params.b + [1].map(params, params)[0]");
+        assert_eq!(plan.globals, vec![Arc::new("params".to_string())]);
+        assert!(plan
+            .nodes
+            .iter()
+            .any(|node| matches!(node.kind, NodeKind::Local(0))));
+    }
+}
@@ -1,14 +1,14 @@
-use cel_parser::{ast::Literal, Expression};
+use cel_parser::ast::Literal;
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use uuid::Uuid;
 
 use std::{collections::HashMap, sync::Arc};
 
-use crate::{cel_type::*, error::*};
+use crate::{cel_type::*, error::*, interpreter::CelExpression};
 
 pub struct CelResult<'a> {
-    pub expr: &'a Expression,
+    pub expr: &'a CelExpression,
     pub val: CelValue,
 }
 
@@ -44,6 +44,7 @@ impl CelValue {
 #[derive(Debug, PartialEq)]
 pub struct CelMap {
     inner: HashMap<CelKey, CelValue>,
+    type_name: Option<Arc<String>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -59,6 +60,26 @@ impl CelArray {
     pub fn push(&mut self, elem: impl Into<CelValue>) {
         self.inner.push(elem.into());
     }
+
+    pub fn get(&self, idx: usize) -> Option<&CelValue> {
+        self.inner.get(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn contains(&self, elem: &CelValue) -> bool {
+        self.inner.contains(elem)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CelValue> {
+        self.inner.iter()
+    }
 }
 
 impl Default for CelArray {
@@ -71,9 +92,23 @@ impl CelMap {
     pub fn new() -> Self {
         Self {
             inner: HashMap::new(),
+            type_name: None,
+        }
+    }
+
+    /// Creates an empty map representing a message of the given type.
+    pub fn new_message(type_name: impl Into<String>) -> Self {
+        Self {
+            inner: HashMap::new(),
+            type_name: Some(Arc::new(type_name.into())),
         }
     }
 
+    /// Name of the message type this map was constructed as, if any.
+    pub fn type_name(&self) -> Option<&str> {
+        self.type_name.as_ref().map(|name| name.as_str())
+    }
+
     pub fn insert(&mut self, k: impl Into<CelKey>, val: impl Into<CelValue>) {
         self.inner.insert(k.into(), val.into());
     }
@@ -84,6 +119,22 @@ impl CelMap {
             .cloned()
             .unwrap_or(CelValue::Null)
     }
+
+    pub fn contains_key(&self, key: impl Into<CelKey>) -> bool {
+        self.inner.contains_key(&key.into())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &CelKey> {
+        self.inner.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
 }
 
 impl Default for CelMap {
@@ -102,6 +153,18 @@ impl From<HashMap<String, CelValue>> for CelMap {
     }
 }
 
+impl From<CelArray> for CelValue {
+    fn from(a: CelArray) -> Self {
+        CelValue::List(Arc::from(a))
+    }
+}
+
+impl From<Vec<CelValue>> for CelArray {
+    fn from(inner: Vec<CelValue>) -> Self {
+        Self { inner }
+    }
+}
+
 impl From<CelMap> for CelValue {
     fn from(m: CelMap) -> Self {
         CelValue::Map(Arc::from(m))
@@ -162,7 +225,11 @@ impl From<serde_json::Value> for CelValue {
                 } else if let Some(i) = n.as_i64() {
                     CelValue::Int(i)
                 } else {
-                    unimplemented!()
+                    // Fractional numbers in params are amounts, keep them exact where possible
+                    n.to_string()
+                        .parse()
+                        .map(CelValue::Decimal)
+                        .unwrap_or_else(|_| CelValue::Double(n.as_f64().unwrap_or(f64::NAN)))
                 }
             }
             String(s) => CelValue::String(Arc::from(s)),
@@ -184,7 +251,7 @@ impl From<serde_json::Value> for CelValue {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub enum CelKey {
     Int(i64),
     UInt(u64),
@@ -192,6 +259,33 @@ pub enum CelKey {
     String(Arc<String>),
 }
 
+impl TryFrom<&CelValue> for CelKey {
+    type Error = CelError;
+
+    fn try_from(v: &CelValue) -> Result<Self, Self::Error> {
+        match v {
+            CelValue::Int(i) => Ok(CelKey::Int(*i)),
+            CelValue::UInt(u) => Ok(CelKey::UInt(*u)),
+            CelValue::Bool(b) => Ok(CelKey::Bool(*b)),
+            CelValue::String(s) => Ok(CelKey::String(s.clone())),
+            _ => Err(CelError::Unexpected(
+                "Expression didn't resolve to a valid key".to_string(),
+            )),
+        }
+    }
+}
+
+impl From<&CelKey> for CelValue {
+    fn from(k: &CelKey) -> Self {
+        match k {
+            CelKey::Int(i) => CelValue::Int(*i),
+            CelKey::UInt(u) => CelValue::UInt(*u),
+            CelKey::Bool(b) => CelValue::Bool(*b),
+            CelKey::String(s) => CelValue::String(s.clone()),
+        }
+    }
+}
+
 impl From<&str> for CelKey {
     fn from(s: &str) -> Self {
         CelKey::String(Arc::from(s.to_string()))
@@ -230,18 +324,27 @@ impl From<&CelValue> for CelType {
     }
 }
 
-impl From<&Literal> for CelValue {
-    fn from(l: &Literal) -> Self {
+impl TryFrom<&Literal> for CelValue {
+    type Error = CelError;
+
+    fn try_from(l: &Literal) -> Result<Self, Self::Error> {
         use Literal::*;
-        match l {
+        Ok(match l {
             Int(i) => CelValue::Int(*i),
             UInt(u) => CelValue::UInt(*u),
-            Double(d) => CelValue::Double(d.parse().expect("Couldn't parse Decimal")),
+            Double(d) => CelValue::Double(
+                d.parse()
+                    .map_err(|e| CelError::Unexpected(format!("{e:?}")))?,
+            ),
+            Decimal(d) => CelValue::Decimal(
+                d.parse()
+                    .map_err(|e| CelError::DecimalError(format!("{e:?}")))?,
+            ),
             String(s) => CelValue::String(s.clone()),
             Bytes(b) => CelValue::Bytes(b.clone()),
             Bool(b) => CelValue::Bool(*b),
             Null => CelValue::Null,
-        }
+        })
     }
 }
 
@@ -265,7 +368,7 @@ impl<'a> TryFrom<CelResult<'a>> for NaiveDate {
             Ok(d)
         } else {
             Err(CelError::EvaluationError(
-                format!("{expr:?}"),
+                expr.location(None),
                 Box::new(CelError::BadType(CelType::Date, CelType::from(&val))),
             ))
         }
@@ -280,7 +383,7 @@ impl<'a> TryFrom<CelResult<'a>> for Uuid {
             Ok(id)
         } else {
             Err(CelError::EvaluationError(
-                format!("{expr:?}"),
+                expr.location(None),
                 Box::new(CelError::BadType(CelType::Uuid, CelType::from(&val))),
             ))
         }
@@ -295,7 +398,7 @@ impl<'a> TryFrom<CelResult<'a>> for String {
             Ok(s.to_string())
         } else {
             Err(CelError::EvaluationError(
-                format!("{expr:?}"),
+                expr.location(None),
                 Box::new(CelError::BadType(CelType::String, CelType::from(&val))),
             ))
         }
@@ -309,7 +412,7 @@ impl<'a> TryFrom<CelResult<'a>> for Decimal {
         match val {
             CelValue::Decimal(n) => Ok(n),
             _ => Err(CelError::EvaluationError(
-                format!("{expr:?}"),
+                expr.location(None),
                 Box::new(CelError::BadType(CelType::Decimal, CelType::from(&val))),
             )),
         }
@@ -353,6 +456,7 @@ impl<'a> TryFrom<CelResult<'a>> for serde_json::Value {
             CelValue::Null => Value::Null,
             CelValue::Date(d) => Value::from(d.to_string()),
             CelValue::Uuid(u) => Value::from(u.to_string()),
+            CelValue::Decimal(d) => Value::from(d.to_string()),
             CelValue::Map(m) => {
                 let mut res = serde_json::Map::new();
                 for (k, v) in m.inner.iter() {
@@ -375,7 +479,34 @@ impl<'a> TryFrom<CelResult<'a>> for serde_json::Value {
                 }
                 Value::from(res)
             }
-            _ => unimplemented!(),
+            CelValue::Bytes(_) => {
+                return Err(CelError::InvalidConversion(
+                    "Cannot convert bytes to JSON".to_string(),
+                ))
+            }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_numbers() {
+        let value =
+            |json: &str| CelValue::from(serde_json::from_str::<serde_json::Value>(json).unwrap());
+        assert_eq!(value("1"), CelValue::UInt(1));
+        assert_eq!(value("-1"), CelValue::Int(-1));
+        assert_eq!(value("12.50"), CelValue::Decimal("12.50".parse().unwrap()));
+        assert_eq!(value("-0.1"), CelValue::Decimal("-0.1".parse().unwrap()));
+        assert_eq!(value("1e300"), CelValue::Double(1e300));
+    }
+
+    #[test]
+    fn bytes_are_not_json() {
+        let expression: CelExpression = "{'k': b'x'}".parse().unwrap();
+        let res = expression.try_evaluate::<serde_json::Value, _>(&crate::CelContext::new());
+        assert!(matches!(res, Err(CelError::InvalidConversion(_))));
+    }
+}
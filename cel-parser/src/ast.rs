@@ -41,7 +41,14 @@ pub enum LeftRightOp {
     Arithmetic(ArithmeticOp),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Byte offsets of an expression within the source it was parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
 pub enum Expression {
     Ternary(Box<Expression>, Box<Expression>, Box<Expression>),
     Relation(RelationOp, Box<Expression>, Box<Expression>),
@@ -56,9 +63,34 @@ pub enum Expression {
 
     Literal(Literal),
     Ident(Arc<String>),
+
+    /// Position of the wrapped expression in the source.
+    /// Spans are ignored when comparing expressions.
+    Spanned(Span, Box<Expression>),
 }
 
 impl Expression {
+    pub(crate) fn spanned(start: usize, end: usize, expr: Expression) -> Self {
+        Expression::Spanned(Span { start, end }, Box::new(expr))
+    }
+
+    /// Returns the expression with any span information peeled off.
+    pub fn unspanned(&self) -> &Expression {
+        let mut expr = self;
+        while let Expression::Spanned(_, inner) = expr {
+            expr = inner;
+        }
+        expr
+    }
+
+    /// Returns the span of the outermost positioned node, if any.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expression::Spanned(span, _) => Some(*span),
+            _ => None,
+        }
+    }
+
     pub(crate) fn from_op(op: LeftRightOp, left: Box<Expression>, right: Box<Expression>) -> Self {
         use LeftRightOp::*;
         match op {
@@ -78,6 +110,25 @@ impl Expression {
     }
 }
 
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        use Expression::*;
+        match (self.unspanned(), other.unspanned()) {
+            (Ternary(c1, l1, r1), Ternary(c2, l2, r2)) => c1 == c2 && l1 == l2 && r1 == r2,
+            (Relation(o1, l1, r1), Relation(o2, l2, r2)) => o1 == o2 && l1 == l2 && r1 == r2,
+            (Arithmetic(o1, l1, r1), Arithmetic(o2, l2, r2)) => o1 == o2 && l1 == l2 && r1 == r2,
+            (Unary(o1, e1), Unary(o2, e2)) => o1 == o2 && e1 == e2,
+            (Member(e1, m1), Member(e2, m2)) => e1 == e2 && m1 == m2,
+            (List(l1), List(l2)) => l1 == l2,
+            (Map(m1), Map(m2)) => m1 == m2,
+            (Struct(n1, f1), Struct(n2, f2)) => n1 == n2 && f1 == f2,
+            (Literal(l1), Literal(l2)) => l1 == l2,
+            (Ident(i1), Ident(i2)) => i1 == i2,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Member {
     Attribute(Arc<String>),
@@ -90,16 +141,46 @@ pub enum Literal {
     Int(i64),
     UInt(u64),
     Double(Arc<String>),
+    Decimal(Arc<String>),
     String(Arc<String>),
     Bytes(Arc<Vec<u8>>),
     Bool(bool),
     Null,
 }
 
+/// Parses an integer literal with an optional sign, `0x` prefix and `u` suffix.
+pub(crate) fn parse_int_literal(s: &str) -> Result<Literal, &'static str> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (unsigned, s) = match s.strip_suffix(['u', 'U']) {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (radix, digits) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(digits) => (16, digits),
+        None => (10, s),
+    };
+    if unsigned {
+        return u64::from_str_radix(digits, radix)
+            .map(Literal::UInt)
+            .map_err(|_| "uint literal out of range");
+    }
+    let magnitude = u64::from_str_radix(digits, radix).map_err(|_| "int literal out of range")?;
+    if negative {
+        0i64.checked_sub_unsigned(magnitude)
+    } else {
+        i64::try_from(magnitude).ok()
+    }
+    .map(Literal::Int)
+    .ok_or("int literal out of range")
+}
+
 #[cfg(test)]
 mod tests {
     use crate::parser::ExpressionParser;
-    use crate::{ArithmeticOp::*, Expression, Expression::*, Literal::*, Member::*};
+    use crate::{ArithmeticOp::*, Expression, Expression::*, Literal::*, Member::*, Span};
 
     fn parse(input: &str) -> Expression {
         ExpressionParser::new()
@@ -144,11 +225,29 @@ mod tests {
         assert_parse_eq("1", Literal(Int(1)))
     }
 
+    #[test]
+    fn int_literals() {
+        assert_parse_eq("0x1F", Literal(Int(31)));
+        assert_parse_eq("-0x1F", Literal(Int(-31)));
+        assert_parse_eq("42u", Literal(UInt(42)));
+        assert_parse_eq("0xFFu", Literal(UInt(255)));
+        assert_parse_eq("-9223372036854775808", Literal(Int(i64::MIN)));
+        assert!(ExpressionParser::new()
+            .parse("9223372036854775808")
+            .is_err());
+    }
+
     #[test]
     fn simple_float() {
         assert_parse_eq("1.0", Literal(Double("1.0".to_string().into())))
     }
 
+    #[test]
+    fn decimal_literal() {
+        assert_parse_eq("1.5m", Literal(Decimal("1.5".to_string().into())));
+        assert_parse_eq("-10M", Literal(Decimal("-10".to_string().into())));
+    }
+
     #[test]
     fn lookup() {
         assert_parse_eq(
@@ -174,4 +273,15 @@ mod tests {
             ),
         )
     }
+
+    #[test]
+    fn spans() {
+        let expr = parse("a.b + c[1]");
+        assert_eq!(expr.span(), Some(Span { start: 0, end: 10 }));
+        let Arithmetic(_, left, right) = expr.unspanned() else {
+            panic!("expected arithmetic")
+        };
+        assert_eq!(left.span(), Some(Span { start: 0, end: 3 }));
+        assert_eq!(right.span(), Some(Span { start: 6, end: 10 }));
+    }
 }
@@ -3,7 +3,10 @@
 
 use lalrpop_util::lalrpop_mod;
 
+pub use lalrpop_util::ParseError;
+
 pub mod ast;
+mod printer;
 
 pub use ast::*;
 
@@ -0,0 +1,326 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::ast::*;
+
+// Binding strength of each grammar tier, loosest first.
+const TERNARY: u8 = 0;
+const OR: u8 = 1;
+const AND: u8 = 2;
+const RELATION: u8 = 3;
+const ADDITION: u8 = 4;
+const MULTIPLICATION: u8 = 5;
+const UNARY: u8 = 6;
+const MEMBER: u8 = 7;
+
+/// Prints the expression in canonical form.
+///
+/// Parentheses are only emitted where operator precedence requires them,
+/// so parsing the output yields an expression equal to the original.
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_expr(f, self, TERNARY)
+    }
+}
+
+impl Display for RelationOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RelationOp::LessThan => "<",
+            RelationOp::LessThanEq => "<=",
+            RelationOp::GreaterThan => ">",
+            RelationOp::GreaterThanEq => ">=",
+            RelationOp::Equals => "==",
+            RelationOp::NotEquals => "!=",
+            RelationOp::In => "in",
+        })
+    }
+}
+
+impl Display for ArithmeticOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArithmeticOp::Add => "+",
+            ArithmeticOp::Subtract => "-",
+            ArithmeticOp::Divide => "/",
+            ArithmeticOp::Multiply => "*",
+            ArithmeticOp::Modulus => "%",
+        })
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Not => "!",
+            UnaryOp::DoubleNot => "!!",
+            UnaryOp::Minus => "-",
+            UnaryOp::DoubleMinus => "--",
+        })
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Int(i) => write!(f, "{i}"),
+            Literal::UInt(u) => write!(f, "{u}u"),
+            Literal::Double(d) if d.contains(['.', 'e', 'E']) => f.write_str(d),
+            Literal::Double(d) => write!(f, "{d}.0"),
+            Literal::Decimal(d) => write!(f, "{d}m"),
+            Literal::String(s) => write_string(f, s),
+            // Bytes literals keep their source text, prefix and quotes included
+            Literal::Bytes(b) => f.write_str(&String::from_utf8_lossy(b)),
+            Literal::Bool(b) => write!(f, "{b}"),
+            Literal::Null => f.write_str("null"),
+        }
+    }
+}
+
+/// The tier an expression is parsed at, ie. the loosest context
+/// it can appear in without parentheses.
+fn precedence(expr: &Expression) -> u8 {
+    match expr {
+        Expression::Spanned(_, inner) => precedence(inner),
+        Expression::Ternary(_, left, right) => match logic_op(left, right) {
+            Some(LogicOp::Or) => OR,
+            Some(LogicOp::And) => AND,
+            None => TERNARY,
+        },
+        Expression::Relation(..) => RELATION,
+        Expression::Arithmetic(ArithmeticOp::Add | ArithmeticOp::Subtract, ..) => ADDITION,
+        Expression::Arithmetic(..) => MULTIPLICATION,
+        Expression::Unary(..) => UNARY,
+        _ => MEMBER,
+    }
+}
+
+/// `a || b` and `a && b` are parsed into ternaries, print them back as such.
+fn logic_op(left: &Expression, right: &Expression) -> Option<LogicOp> {
+    match (left.unspanned(), right.unspanned()) {
+        (Expression::Literal(Literal::Bool(true)), _) => Some(LogicOp::Or),
+        (_, Expression::Literal(Literal::Bool(false))) => Some(LogicOp::And),
+        _ => None,
+    }
+}
+
+fn write_expr(f: &mut Formatter<'_>, expr: &Expression, min: u8) -> fmt::Result {
+    let expr = expr.unspanned();
+    if precedence(expr) < min {
+        f.write_char('(')?;
+        write_expr(f, expr, TERNARY)?;
+        return f.write_char(')');
+    }
+    match expr {
+        Expression::Ternary(cond, left, right) => match logic_op(left, right) {
+            Some(LogicOp::Or) => write_binary(f, cond, "||", right, OR),
+            Some(LogicOp::And) => write_binary(f, cond, "&&", left, AND),
+            None => {
+                write_expr(f, cond, OR)?;
+                f.write_str(" ? ")?;
+                write_expr(f, left, OR)?;
+                f.write_str(" : ")?;
+                write_expr(f, right, TERNARY)
+            }
+        },
+        Expression::Relation(op, left, right) => {
+            write_binary(f, left, &op.to_string(), right, RELATION)
+        }
+        Expression::Arithmetic(op, left, right) => {
+            write_binary(f, left, &op.to_string(), right, precedence(expr))
+        }
+        Expression::Unary(op, operand) => {
+            let operand = operand_to_string(operand);
+            let minus = matches!(op, UnaryOp::Minus | UnaryOp::DoubleMinus);
+            // Keep `- 1` from being read back as the literal `-1`
+            if minus && operand.starts_with(|c: char| c.is_ascii_digit() || "-+.".contains(c)) {
+                write!(f, "{op} {operand}")
+            } else {
+                write!(f, "{op}{operand}")
+            }
+        }
+        Expression::Member(target, member) => match member.as_ref() {
+            Member::Attribute(name) => {
+                write_expr(f, target, MEMBER)?;
+                write!(f, ".{name}")
+            }
+            Member::Index(idx) => {
+                write_expr(f, target, MEMBER)?;
+                f.write_char('[')?;
+                write_expr(f, idx, TERNARY)?;
+                f.write_char(']')
+            }
+            Member::FunctionCall(args) => {
+                write_expr(f, target, MEMBER)?;
+                f.write_char('(')?;
+                write_list(f, args, |f, arg| write_expr(f, arg, TERNARY))?;
+                f.write_char(')')
+            }
+        },
+        Expression::List(items) => {
+            f.write_char('[')?;
+            write_list(f, items, |f, item| write_expr(f, item, TERNARY))?;
+            f.write_char(']')
+        }
+        Expression::Map(entries) => {
+            f.write_char('{')?;
+            write_list(f, entries, |f, (k, v)| {
+                write_expr(f, k, TERNARY)?;
+                f.write_str(": ")?;
+                write_expr(f, v, TERNARY)
+            })?;
+            f.write_char('}')
+        }
+        Expression::Struct(names, fields) => {
+            // The grammar separates the parts of a type name by whitespace
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(' ')?;
+                }
+                f.write_str(name)?;
+            }
+            f.write_char('{')?;
+            write_list(f, fields, |f, (name, v)| {
+                write!(f, "{name}: ")?;
+                write_expr(f, v, TERNARY)
+            })?;
+            f.write_char('}')
+        }
+        Expression::Literal(literal) => write!(f, "{literal}"),
+        Expression::Ident(name) => f.write_str(name),
+        Expression::Spanned(..) => unreachable!("spans are peeled off above"),
+    }
+}
+
+/// Binary operators are left associative, so the right operand binds one tier tighter.
+fn write_binary(
+    f: &mut Formatter<'_>,
+    left: &Expression,
+    op: &str,
+    right: &Expression,
+    tier: u8,
+) -> fmt::Result {
+    write_expr(f, left, tier)?;
+    write!(f, " {op} ")?;
+    write_expr(f, right, tier + 1)
+}
+
+fn operand_to_string(operand: &Expression) -> String {
+    struct Operand<'a>(&'a Expression);
+    impl Display for Operand<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write_expr(f, self.0, MEMBER)
+        }
+    }
+    Operand(operand).to_string()
+}
+
+fn write_list<T>(
+    f: &mut Formatter<'_>,
+    items: &[T],
+    mut write_item: impl FnMut(&mut Formatter<'_>, &T) -> fmt::Result,
+) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write_item(f, item)?;
+    }
+    Ok(())
+}
+
+/// String literals hold their source text without the quotes (escapes are not
+/// processed), so pick a quote style the text can be wrapped in unchanged.
+fn write_string(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    let quote = if fits_quotes(s, '\'') {
+        "'"
+    } else if fits_quotes(s, '"') {
+        "\""
+    } else if !s.contains("'''") {
+        "'''"
+    } else {
+        "\"\"\""
+    };
+    write!(f, "{quote}{s}{quote}")
+}
+
+fn fits_quotes(s: &str, quote: char) -> bool {
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.next().is_none() => return false,
+            '\\' => (),
+            '\n' => return false,
+            c if c == quote => return false,
+            _ => (),
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::ExpressionParser;
+
+    fn parse(source: &str) -> crate::Expression {
+        ExpressionParser::new()
+            .parse(source)
+            .unwrap_or_else(|e| panic!("{source}: {e}"))
+    }
+
+    fn assert_prints(source: &str, expected: &str) {
+        let expr = parse(source);
+        let printed = expr.to_string();
+        assert_eq!(printed, expected);
+        assert_eq!(parse(&printed), expr, "{printed} doesn't round trip");
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        assert_prints("(1 + 2) * 3", "(1 + 2) * 3");
+        assert_prints("1 + (2 * 3)", "1 + 2 * 3");
+        assert_prints("(1 + 2) + 3", "1 + 2 + 3");
+        assert_prints("1 - (2 - 3)", "1 - (2 - 3)");
+        assert_prints("((a))", "a");
+        assert_prints("(a || b) && c", "(a || b) && c");
+        assert_prints("a || (b && c)", "a || b && c");
+        assert_prints(
+            "(a ? b : c) ? d : (e ? f : g)",
+            "(a ? b : c) ? d : e ? f : g",
+        );
+        assert_prints("a < b == (c < d)", "a < b == (c < d)");
+        assert_prints("-(a + b)", "-(a + b)");
+        assert_prints("!(!a)", "!(!a)");
+        assert_prints("(a + b).size()", "(a + b).size()");
+    }
+
+    #[test]
+    fn members_and_collections() {
+        assert_prints(
+            "params.items.map( x , x*2 )[0]",
+            "params.items.map(x, x * 2)[0]",
+        );
+        assert_prints(
+            "has(a.b) && size([1,2]) > 0",
+            "has(a.b) && size([1, 2]) > 0",
+        );
+        assert_prints("{'a' : 1, \"b\": [ ] }", "{'a': 1, 'b': []}");
+        assert_prints(
+            "uuid('6b7a3f1e-4a3e-4c0e-9d4b-3e4f7a0b1c2d')",
+            "uuid('6b7a3f1e-4a3e-4c0e-9d4b-3e4f7a0b1c2d')",
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_prints("-1", "-1");
+        assert_prints("- 1", "- 1");
+        assert_prints("--1", "- -1");
+        assert_prints("a - -1", "a - -1");
+        assert_prints("0xFF + 3U", "255 + 3u");
+        assert_prints("1.5e3 + 2.25", "1.5e3 + 2.25");
+        assert_prints("10.50m", "10.50m");
+        assert_prints("\"it's\"", "\"it's\"");
+        assert_prints("b'abc'", "b'abc'");
+        assert_prints("true && null == false", "true && null == false");
+    }
+}
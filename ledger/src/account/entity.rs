@@ -1,14 +1,17 @@
 use chrono::{DateTime, Utc};
 use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
 
 use crate::primitives::*;
 
 /// Representation of a ledger account entity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account<M> {
     pub id: AccountId,
     pub code: String,
     pub name: String,
     pub normal_balance_type: DebitOrCredit,
+    pub category: Option<AccountCategory>,
     pub description: Option<String>,
     pub status: Status,
     pub metadata: Option<M>,
@@ -17,8 +20,64 @@ pub struct Account<M> {
     pub created_at: DateTime<Utc>,
 }
 
+/// A version of an account together with what changed since the previous one.
+#[derive(Debug, Clone)]
+pub struct AccountVersion<M> {
+    pub account: Account<M>,
+    /// Empty for the first version.
+    pub changes: Vec<AccountChange>,
+}
+
+/// A field that differs between two consecutive versions of an account.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountChange {
+    Description {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Metadata {
+        from: Option<serde_json::Value>,
+        to: Option<serde_json::Value>,
+    },
+    Status {
+        from: Status,
+        to: Status,
+    },
+}
+
+/// Criteria for listing accounts, unset fields match every account.
+#[derive(Debug, Clone, Default)]
+pub struct AccountFilter {
+    pub status: Option<Status>,
+    pub normal_balance_type: Option<DebitOrCredit>,
+    pub category: Option<AccountCategory>,
+    pub name_prefix: Option<String>,
+    /// Matches accounts whose metadata contains this JSON (`@>`).
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Position of an account in the listing order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: AccountId,
+}
+
+impl<M> From<&Account<M>> for AccountCursor {
+    fn from(account: &Account<M>) -> Self {
+        Self {
+            created_at: account.created_at,
+            id: account.id,
+        }
+    }
+}
+
 /// Representation of a ***new*** ledger account entity with required/optional properties and a builder.
+///
+/// `normal_balance_type` defaults to the one of the `category` and building
+/// fails if both are set and disagree, as does inserting such an account.
 #[derive(Builder, Debug)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct NewAccount {
     #[builder(setter(into))]
     pub id: AccountId,
@@ -26,8 +85,10 @@ pub struct NewAccount {
     pub(super) code: String,
     #[builder(setter(into))]
     pub(super) name: String,
-    #[builder(default)]
+    #[builder(default = "self.default_normal_balance_type()")]
     pub(super) normal_balance_type: DebitOrCredit,
+    #[builder(setter(strip_option), default)]
+    pub(super) category: Option<AccountCategory>,
     #[builder(setter(strip_option, into), default)]
     pub(super) description: Option<String>,
     #[builder(default)]
@@ -50,6 +111,26 @@ impl NewAccountBuilder {
         self.metadata = Some(Some(serde_json::to_value(metadata)?));
         Ok(self)
     }
+
+    fn default_normal_balance_type(&self) -> DebitOrCredit {
+        match self.category {
+            Some(Some(category)) => category.normal_balance_type(),
+            _ => DebitOrCredit::default(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let (Some(Some(category)), Some(normal_balance_type)) =
+            (self.category, self.normal_balance_type)
+        {
+            if category.normal_balance_type() != normal_balance_type {
+                return Err(format!(
+                    "normal_balance_type {normal_balance_type:?} doesn't match category {category:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -91,4 +172,35 @@ mod tests {
             .unwrap();
         assert_eq!(new_account.metadata, Some(json!({"foo": "bar"})));
     }
+
+    #[test]
+    fn normal_balance_type_follows_category() {
+        let new_account = NewAccount::builder()
+            .id(uuid::Uuid::new_v4())
+            .code("code")
+            .name("name")
+            .category(AccountCategory::Expense)
+            .build()
+            .unwrap();
+        assert_eq!(new_account.normal_balance_type, DebitOrCredit::Debit);
+
+        let new_account = NewAccount::builder()
+            .id(uuid::Uuid::new_v4())
+            .code("code")
+            .name("name")
+            .category(AccountCategory::ContraAsset)
+            .normal_balance_type(DebitOrCredit::Credit)
+            .build()
+            .unwrap();
+        assert_eq!(new_account.category, Some(AccountCategory::ContraAsset));
+
+        let new_account = NewAccount::builder()
+            .id(uuid::Uuid::new_v4())
+            .code("code")
+            .name("name")
+            .category(AccountCategory::Revenue)
+            .normal_balance_type(DebitOrCredit::Debit)
+            .build();
+        assert!(new_account.is_err());
+    }
 }
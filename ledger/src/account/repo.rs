@@ -1,9 +1,11 @@
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
 use sqlx::{Pool, Postgres, Transaction};
 use tracing::instrument;
+use uuid::Uuid;
 
 use super::entity::*;
-use crate::{error::*, primitives::*};
+use crate::{error::*, pagination::*, primitives::*};
 
 /// Repository for working with `Account` entities.
 #[derive(Debug, Clone)]
@@ -34,18 +36,20 @@ impl Accounts {
             code,
             name,
             normal_balance_type,
+            category,
             description,
             status,
             metadata,
         } = new_account;
         let record = sqlx::query!(
-            r#"INSERT INTO sqlx_ledger_accounts (id, code, name, normal_balance_type, description, status, metadata)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            r#"INSERT INTO sqlx_ledger_accounts (id, code, name, normal_balance_type, category, description, status, metadata)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             RETURNING id, version, created_at"#,
             id as AccountId,
             code,
             name,
             normal_balance_type as DebitOrCredit,
+            category as Option<AccountCategory>,
             description,
             status as Status,
             metadata
@@ -77,14 +81,247 @@ impl Accounts {
         Ok(id)
     }
 
+    /// Like [update](Self::update) but only succeeds if `expected_version` is
+    /// still the latest version of the account, otherwise the update fails with
+    /// [SqlxLedgerError::OptimisticLockingError].
+    #[instrument(name = "sqlx_ledger.accounts.update_with_version", skip(self))]
+    pub async fn update_with_version<T: Serialize + std::fmt::Debug>(
+        &self,
+        id: AccountId,
+        expected_version: u32,
+        description: Option<String>,
+        metadata: Option<T>,
+    ) -> Result<AccountId, SqlxLedgerError> {
+        let metadata_json = match metadata {
+            Some(m) => Some(serde_json::to_value(m)?),
+            None => None,
+        };
+        // A newer version makes the insert collide with it on (id, version)
+        let result = sqlx::query_file!(
+            "src/account/sql/update-account-with-version.sql",
+            id as AccountId,
+            expected_version as i32,
+            description,
+            metadata_json
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match SqlxLedgerError::from(e) {
+            SqlxLedgerError::DuplicateKey(_) => SqlxLedgerError::OptimisticLockingError,
+            e => e,
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(SqlxLedgerError::OptimisticLockingError);
+        }
+        Ok(id)
+    }
+
+    #[instrument(name = "sqlx_ledger.accounts.find_by_id", skip(self))]
+    pub async fn find_by_id<M: DeserializeOwned>(
+        &self,
+        id: AccountId,
+    ) -> Result<Option<Account<M>>, SqlxLedgerError> {
+        let record = sqlx::query_as!(
+            AccountRow,
+            r#"SELECT id, version, code, name, description, status AS "status: Status", normal_balance_type AS "normal_balance_type: DebitOrCredit", category AS "category: AccountCategory", metadata, modified_at, created_at
+            FROM sqlx_ledger_accounts
+            WHERE id = $1
+            ORDER BY version DESC LIMIT 1"#,
+            id as AccountId
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        record.map(Account::try_from).transpose()
+    }
+
     #[instrument(name = "sqlx_ledger.accounts.find_by_code", skip(self))]
-    pub async fn find_by_code(&self, code: &str) -> Result<Option<AccountId>, SqlxLedgerError> {
-        let record = sqlx::query!(
-            r#"SELECT id FROM sqlx_ledger_accounts WHERE code = $1 LIMIT 1"#,
+    pub async fn find_by_code<M: DeserializeOwned>(
+        &self,
+        code: &str,
+    ) -> Result<Option<Account<M>>, SqlxLedgerError> {
+        let record = sqlx::query_as!(
+            AccountRow,
+            r#"SELECT id, version, code, name, description, status AS "status: Status", normal_balance_type AS "normal_balance_type: DebitOrCredit", category AS "category: AccountCategory", metadata, modified_at, created_at
+            FROM sqlx_ledger_accounts
+            WHERE code = $1
+            ORDER BY version DESC LIMIT 1"#,
             code
         )
         .fetch_optional(&self.pool)
         .await?;
-        Ok(record.map(|r| AccountId::from(r.id)))
+        record.map(Account::try_from).transpose()
+    }
+
+    #[instrument(name = "sqlx_ledger.accounts.find_many", skip(self, ids))]
+    pub async fn find_many<M: DeserializeOwned>(
+        &self,
+        ids: impl IntoIterator<Item = impl std::borrow::Borrow<AccountId>>,
+    ) -> Result<Vec<Account<M>>, SqlxLedgerError> {
+        let ids: Vec<_> = ids.into_iter().map(|id| Uuid::from(id.borrow())).collect();
+        let records = sqlx::query_as!(
+            AccountRow,
+            r#"SELECT id, version, code, name, description, status AS "status: Status", normal_balance_type AS "normal_balance_type: DebitOrCredit", category AS "category: AccountCategory", metadata, modified_at, created_at
+            FROM sqlx_ledger_accounts a
+            WHERE id = ANY($1)
+            AND version = (SELECT MAX(version) FROM sqlx_ledger_accounts WHERE id = a.id)"#,
+            &ids[..]
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        records.into_iter().map(Account::try_from).collect()
+    }
+
+    /// Returns every version of the account, oldest first, with the fields
+    /// that changed relative to the version before it.
+    #[instrument(name = "sqlx_ledger.accounts.history", skip(self))]
+    pub async fn history<M: DeserializeOwned>(
+        &self,
+        id: AccountId,
+    ) -> Result<Vec<AccountVersion<M>>, SqlxLedgerError> {
+        let records = sqlx::query_as!(
+            AccountRow,
+            r#"SELECT id, version, code, name, description, status AS "status: Status", normal_balance_type AS "normal_balance_type: DebitOrCredit", category AS "category: AccountCategory", metadata, modified_at, created_at
+            FROM sqlx_ledger_accounts
+            WHERE id = $1
+            ORDER BY version"#,
+            id as AccountId
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut versions = Vec::with_capacity(records.len());
+        let mut previous: Option<AccountRow> = None;
+        for row in records {
+            let changes = previous
+                .as_ref()
+                .map(|previous| previous.changes(&row))
+                .unwrap_or_default();
+            previous = Some(row.clone());
+            versions.push(AccountVersion {
+                account: Account::try_from(row)?,
+                changes,
+            });
+        }
+        Ok(versions)
+    }
+
+    /// Lists the latest version of the accounts matching `filter`,
+    /// ordered by creation.
+    #[instrument(name = "sqlx_ledger.accounts.list", skip(self))]
+    pub async fn list<M: DeserializeOwned>(
+        &self,
+        filter: AccountFilter,
+        query: PaginatedQueryArgs<AccountCursor>,
+    ) -> Result<PaginatedQueryRet<Account<M>, AccountCursor>, SqlxLedgerError> {
+        let mut tx = self.pool.begin().await?;
+        let res = self.list_in_tx(&mut tx, filter, query).await?;
+        tx.commit().await?;
+        Ok(res)
+    }
+
+    pub(crate) async fn list_in_tx<M: DeserializeOwned>(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        filter: AccountFilter,
+        query: PaginatedQueryArgs<AccountCursor>,
+    ) -> Result<PaginatedQueryRet<Account<M>, AccountCursor>, SqlxLedgerError> {
+        let (after_created_at, after_id) = match query.after {
+            Some(AccountCursor { created_at, id }) => (Some(created_at), Some(id)),
+            None => (None, None),
+        };
+        let records = sqlx::query_as!(
+            AccountRow,
+            r#"SELECT id, version, code, name, description, status AS "status: Status", normal_balance_type AS "normal_balance_type: DebitOrCredit", category AS "category: AccountCategory", metadata, modified_at, created_at
+            FROM sqlx_ledger_accounts a
+            WHERE version = (SELECT MAX(version) FROM sqlx_ledger_accounts WHERE id = a.id)
+            AND ($1::Status IS NULL OR status = $1)
+            AND ($2::DebitOrCredit IS NULL OR normal_balance_type = $2)
+            AND ($3::AccountCategory IS NULL OR category = $3)
+            AND ($4::VARCHAR IS NULL OR starts_with(name, $4))
+            AND ($5::JSONB IS NULL OR metadata @> $5)
+            AND ($6::TIMESTAMPTZ IS NULL OR (created_at, id) > ($6, $7))
+            ORDER BY created_at, id
+            LIMIT $8"#,
+            filter.status as Option<Status>,
+            filter.normal_balance_type as Option<DebitOrCredit>,
+            filter.category as Option<AccountCategory>,
+            filter.name_prefix,
+            filter.metadata,
+            after_created_at,
+            after_id as Option<AccountId>,
+            (query.first + 1) as i64
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        let has_next_page = records.len() > query.first;
+        let entities = records
+            .into_iter()
+            .take(query.first)
+            .map(Account::try_from)
+            .collect::<Result<Vec<Account<M>>, _>>()?;
+        Ok(PaginatedQueryRet {
+            end_cursor: entities.last().map(AccountCursor::from),
+            entities,
+            has_next_page,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct AccountRow {
+    id: Uuid,
+    version: i32,
+    code: String,
+    name: String,
+    description: Option<String>,
+    status: Status,
+    normal_balance_type: DebitOrCredit,
+    category: Option<AccountCategory>,
+    metadata: Option<serde_json::Value>,
+    modified_at: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+}
+
+impl AccountRow {
+    fn changes(&self, next: &AccountRow) -> Vec<AccountChange> {
+        let mut changes = Vec::new();
+        if self.description != next.description {
+            changes.push(AccountChange::Description {
+                from: self.description.clone(),
+                to: next.description.clone(),
+            });
+        }
+        if self.metadata != next.metadata {
+            changes.push(AccountChange::Metadata {
+                from: self.metadata.clone(),
+                to: next.metadata.clone(),
+            });
+        }
+        if self.status != next.status {
+            changes.push(AccountChange::Status {
+                from: self.status,
+                to: next.status,
+            });
+        }
+        changes
+    }
+}
+
+impl<M: DeserializeOwned> TryFrom<AccountRow> for Account<M> {
+    type Error = SqlxLedgerError;
+
+    fn try_from(row: AccountRow) -> Result<Self, Self::Error> {
+        Ok(Account {
+            id: AccountId::from(row.id),
+            code: row.code,
+            name: row.name,
+            normal_balance_type: row.normal_balance_type,
+            category: row.category,
+            description: row.description,
+            status: row.status,
+            metadata: row.metadata.map(serde_json::from_value).transpose()?,
+            version: row.version as u32,
+            modified_at: row.modified_at,
+            created_at: row.created_at,
+        })
     }
 }
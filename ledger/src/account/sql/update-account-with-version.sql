@@ -0,0 +1,6 @@
+INSERT INTO sqlx_ledger_accounts
+  (id, version, code, name, normal_balance_type, category, description, status, metadata, created_at)
+(
+ SELECT id, version + 1, code, name, normal_balance_type, category, COALESCE($3, description), status, COALESCE($4, metadata), created_at
+ FROM sqlx_ledger_accounts WHERE id = $1 AND version = $2
+)
@@ -226,18 +226,17 @@ impl Balances {
             r#"INSERT INTO sqlx_ledger_current_balances
                   (journal_id, account_id, currency, version)"#,
         );
-        let mut any_new = false;
-        query_builder.push_values(
-            previous_versions.iter().filter(|(_, v)| **v == 0),
-            |mut builder, ((account_id, currency), version)| {
-                any_new = true;
-                builder.push_bind(journal_id);
-                builder.push_bind(**account_id);
-                builder.push_bind(currency.code());
-                builder.push_bind(version);
-            },
-        );
-        if any_new {
+        let new_accounts: Vec<_> = previous_versions.iter().filter(|(_, v)| **v == 0).collect();
+        if !new_accounts.is_empty() {
+            query_builder.push_values(
+                new_accounts,
+                |mut builder, ((account_id, currency), version)| {
+                    builder.push_bind(journal_id);
+                    builder.push_bind(**account_id);
+                    builder.push_bind(currency.code());
+                    builder.push_bind(version);
+                },
+            );
             query_builder.build().execute(&mut **tx).await?;
         }
         let mut query_builder: QueryBuilder<Postgres> =
@@ -5,6 +5,7 @@ use rust_decimal::Decimal;
 use crate::primitives::*;
 
 /// Representation of a ledger transaction entry entity.
+#[derive(Debug, Clone)]
 pub struct Entry {
     pub id: EntryId,
     pub version: u32,
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use sqlx::{PgPool, Postgres, QueryBuilder, Row, Transaction};
 use tracing::instrument;
@@ -7,7 +7,7 @@ use uuid::Uuid;
 use std::{collections::HashMap, str::FromStr};
 
 use super::entity::*;
-use crate::{error::*, primitives::*};
+use crate::{error::*, ledger::NetEffect, primitives::*};
 
 /// Repository for working with `Entry` (Debit/Credit) entities.
 #[derive(Debug, Clone)]
@@ -108,9 +108,119 @@ impl Entries {
         Ok(ret)
     }
 
+    /// Writes a new version of the entry with the given description.
+    /// Amounts and every other field are immutable.
+    #[instrument(name = "sqlx_ledger.entries.update_description", skip(self))]
+    pub async fn update_description(
+        &self,
+        id: EntryId,
+        description: String,
+    ) -> Result<EntryId, SqlxLedgerError> {
+        self.insert_version(id, None, description).await
+    }
+
+    /// Like [update_description](Self::update_description) but only succeeds if
+    /// `expected_version` is still the latest version of the entry, otherwise the
+    /// update fails with [SqlxLedgerError::OptimisticLockingError].
+    #[instrument(
+        name = "sqlx_ledger.entries.update_description_with_version",
+        skip(self)
+    )]
+    pub async fn update_description_with_version(
+        &self,
+        id: EntryId,
+        expected_version: u32,
+        description: String,
+    ) -> Result<EntryId, SqlxLedgerError> {
+        self.insert_version(id, Some(expected_version), description)
+            .await
+            .map_err(|e| match e {
+                // A newer version makes the insert collide with it on (id, version)
+                SqlxLedgerError::DuplicateKey(_) => SqlxLedgerError::OptimisticLockingError,
+                e => e,
+            })
+    }
+
+    async fn insert_version(
+        &self,
+        id: EntryId,
+        expected_version: Option<u32>,
+        description: String,
+    ) -> Result<EntryId, SqlxLedgerError> {
+        let result = sqlx::query_file!(
+            "src/entry/sql/update-entry-description.sql",
+            id as EntryId,
+            expected_version.map(|v| v as i32),
+            description
+        )
+        .execute(&self.pool)
+        .await?;
+        match (result.rows_affected(), expected_version) {
+            (0, Some(_)) => Err(SqlxLedgerError::OptimisticLockingError),
+            (0, None) => Err(SqlxLedgerError::EntryNotFound(id)),
+            _ => Ok(id),
+        }
+    }
+
+    /// Totals of the settled entries posted to `account_ids` by transactions
+    /// effective on or before `as_of`, per account and currency.
+    #[instrument(
+        level = "trace",
+        name = "sqlx_ledger.entries.settled_totals_in_tx",
+        skip(self, tx)
+    )]
+    pub(crate) async fn settled_totals_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        journal_id: JournalId,
+        account_ids: &[AccountId],
+        as_of: NaiveDate,
+    ) -> Result<Vec<NetEffect>, SqlxLedgerError> {
+        let account_ids: Vec<Uuid> = account_ids.iter().map(Uuid::from).collect();
+        let records = sqlx::query!(
+            r#"SELECT e.account_id, e.currency,
+              COALESCE(SUM(e.units) FILTER (WHERE e.direction = 'debit'), 0) AS "dr_amount!",
+              COALESCE(SUM(e.units) FILTER (WHERE e.direction = 'credit'), 0) AS "cr_amount!"
+            FROM sqlx_ledger_entries e
+            JOIN sqlx_ledger_transactions t ON t.id = e.transaction_id AND t.version = 1
+            WHERE e.journal_id = $1 AND e.version = 1 AND e.layer = 'settled'
+            AND e.account_id = ANY($2) AND t.effective <= $3
+            GROUP BY e.account_id, e.currency
+            ORDER BY e.account_id, e.currency"#,
+            journal_id as JournalId,
+            &account_ids[..],
+            as_of
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        records
+            .into_iter()
+            .map(|row| {
+                Ok(NetEffect {
+                    account_id: AccountId::from(row.account_id),
+                    currency: row.currency.parse()?,
+                    layer: Layer::Settled,
+                    dr_amount: row.dr_amount,
+                    cr_amount: row.cr_amount,
+                })
+            })
+            .collect()
+    }
+
     pub async fn list_by_transaction_ids(
         &self,
         tx_ids: impl IntoIterator<Item = impl std::borrow::Borrow<TransactionId>>,
+    ) -> Result<HashMap<TransactionId, Vec<Entry>>, SqlxLedgerError> {
+        let mut tx = self.pool.begin().await?;
+        let res = self.list_by_transaction_ids_in_tx(&mut tx, tx_ids).await?;
+        tx.commit().await?;
+        Ok(res)
+    }
+
+    pub(crate) async fn list_by_transaction_ids_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        tx_ids: impl IntoIterator<Item = impl std::borrow::Borrow<TransactionId>>,
     ) -> Result<HashMap<TransactionId, Vec<Entry>>, SqlxLedgerError> {
         let tx_ids: Vec<Uuid> = tx_ids
             .into_iter()
@@ -121,7 +231,7 @@ impl Entries {
             FROM sqlx_ledger_entries
             WHERE transaction_id = ANY($1) ORDER BY transaction_id ASC, sequence ASC, version DESC"#,
             &tx_ids[..]
-        ).fetch_all(&self.pool).await?;
+        ).fetch_all(&mut **tx).await?;
 
         let mut transactions: HashMap<TransactionId, Vec<Entry>> = HashMap::new();
 
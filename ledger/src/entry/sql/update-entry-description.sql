@@ -0,0 +1,7 @@
+INSERT INTO sqlx_ledger_entries
+  (id, version, transaction_id, account_id, journal_id, entry_type, layer, units, currency, direction, sequence, description, created_at)
+(
+ SELECT id, version + 1, transaction_id, account_id, journal_id, entry_type, layer, units, currency, direction, sequence, $3, created_at
+ FROM sqlx_ledger_entries WHERE id = $1 AND ($2::INT IS NULL OR version = $2)
+ ORDER BY version DESC LIMIT 1
+)
@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use sqlx::error::DatabaseError;
 use thiserror::Error;
@@ -15,9 +16,13 @@ pub enum SqlxLedgerError {
     #[error("SqlxLedgerError - SerdeJson: {0}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("SqlxLedgerError - SendEvent: {0}")]
-    SendEvent(#[from] tokio::sync::broadcast::error::SendError<SqlxLedgerEvent>),
+    SendEvent(#[from] Box<tokio::sync::broadcast::error::SendError<SqlxLedgerEvent>>),
     #[error("SqlxLedgerError - CelError: {0}")]
     CelError(#[from] CelError),
+    #[error(
+        "SqlxLedgerError - TxTemplateEvaluation: template '{0}' failed to evaluate '{1}': {2}"
+    )]
+    TxTemplateEvaluation(String, String, Box<SqlxLedgerError>),
     #[error("SqlxLedgerError - TxParamTypeMismatch: expected {0:?}")]
     TxParamTypeMismatch(ParamDataType),
     #[error("SqlxLedgerError - TooManyParameters")]
@@ -30,6 +35,20 @@ pub enum SqlxLedgerError {
     UnknownCurrency(String),
     #[error("SqlxLedgerError - UnbalancedTransaction: currency {0} amount {1}")]
     UnbalancedTransaction(Currency, Decimal),
+    #[error("SqlxLedgerError - TransactionNotFound: {0}")]
+    TransactionNotFound(TransactionId),
+    #[error("SqlxLedgerError - EntryNotFound: {0}")]
+    EntryNotFound(EntryId),
+    #[error("SqlxLedgerError - TxTemplateNotFound: {0}")]
+    TxTemplateNotFound(String),
+    #[error("SqlxLedgerError - JournalNotFound: {0}")]
+    JournalNotFound(JournalId),
+    #[error("SqlxLedgerError - JournalNotActive: journal {0} is {1:?}")]
+    JournalNotActive(JournalId, JournalStatus),
+    #[error("SqlxLedgerError - PeriodClosed: journal {0} is closed through {1}")]
+    PeriodClosed(JournalId, NaiveDate),
+    #[error("SqlxLedgerError - NoIncomeCloseToReverse: {0:?}")]
+    NoIncomeCloseToReverse(CorrelationId),
     #[error("SqlxLedgerError - OptimisticLockingError")]
     OptimisticLockingError,
     #[error("SqlxLedgerError - EventSubscriberClosed")]
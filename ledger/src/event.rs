@@ -22,7 +22,8 @@ use std::{
 };
 
 use crate::{
-    balance::BalanceDetails, transaction::Transaction, AccountId, JournalId, SqlxLedgerError,
+    account::Account, balance::BalanceDetails, transaction::Transaction, AccountId, JournalId,
+    SqlxLedgerError,
 };
 
 /// Options when initializing the EventSubscriber
@@ -80,7 +81,9 @@ impl EventSubscriber {
             loop {
                 match incoming.recv().await {
                     Ok(event) => {
-                        let journal_id = event.journal_id();
+                        let Some(journal_id) = event.journal_id() else {
+                            continue;
+                        };
                         if let Some(journal_receivers) =
                             inner_journal_receivers.read().await.get(&journal_id)
                         {
@@ -202,17 +205,21 @@ impl SqlxLedgerEvent {
 }
 
 impl SqlxLedgerEvent {
-    pub fn journal_id(&self) -> JournalId {
+    /// `None` for account events, accounts are not bound to a journal.
+    pub fn journal_id(&self) -> Option<JournalId> {
         match &self.data {
-            SqlxLedgerEventData::BalanceUpdated(b) => b.journal_id,
-            SqlxLedgerEventData::TransactionCreated(t) => t.journal_id,
-            SqlxLedgerEventData::TransactionUpdated(t) => t.journal_id,
+            SqlxLedgerEventData::BalanceUpdated(b) => Some(b.journal_id),
+            SqlxLedgerEventData::TransactionCreated(t) => Some(t.journal_id),
+            SqlxLedgerEventData::TransactionUpdated(t) => Some(t.journal_id),
+            SqlxLedgerEventData::AccountCreated(_) | SqlxLedgerEventData::AccountUpdated(_) => None,
         }
     }
 
     pub fn account_id(&self) -> Option<AccountId> {
         match &self.data {
             SqlxLedgerEventData::BalanceUpdated(b) => Some(b.account_id),
+            SqlxLedgerEventData::AccountCreated(a) => Some(a.id),
+            SqlxLedgerEventData::AccountUpdated(a) => Some(a.id),
             _ => None,
         }
     }
@@ -221,18 +228,24 @@ impl SqlxLedgerEvent {
 /// Represents the different kinds of data that can be included in an `SqlxLedgerEvent` event.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
 pub enum SqlxLedgerEventData {
     BalanceUpdated(BalanceDetails),
     TransactionCreated(Transaction),
     TransactionUpdated(Transaction),
+    AccountCreated(Account<serde_json::Value>),
+    AccountUpdated(Account<serde_json::Value>),
 }
 
 /// Defines possible event types for `SqlxLedgerEvent`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum SqlxLedgerEventType {
     BalanceUpdated,
     TransactionCreated,
     TransactionUpdated,
+    AccountCreated,
+    AccountUpdated,
 }
 
 pub(crate) async fn subscribe(
@@ -323,7 +336,7 @@ fn sqlx_ledger_notification_received(
     if !ignore_gap && last_id.0 + 1 != id.0 {
         return Ok(false);
     }
-    sender.send(event)?;
+    sender.send(event).map_err(Box::new)?;
     *last_id = id;
     Ok(true)
 }
@@ -350,6 +363,12 @@ impl TryFrom<EventRaw> for SqlxLedgerEvent {
             SqlxLedgerEventType::TransactionUpdated => {
                 SqlxLedgerEventData::TransactionUpdated(serde_json::from_value(value.data)?)
             }
+            SqlxLedgerEventType::AccountCreated => {
+                SqlxLedgerEventData::AccountCreated(serde_json::from_value(value.data)?)
+            }
+            SqlxLedgerEventType::AccountUpdated => {
+                SqlxLedgerEventData::AccountUpdated(serde_json::from_value(value.data)?)
+            }
         };
 
         Ok(SqlxLedgerEvent {
@@ -1,19 +1,59 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
 
 use crate::primitives::*;
 
 /// Representation of a ledger journal entity.
+#[derive(Debug, Clone)]
 pub struct Journal {
     pub id: JournalId,
     pub name: String,
     pub description: Option<String>,
-    pub status: Status,
+    pub status: JournalStatus,
     pub version: u32,
     pub modified_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
 }
 
+impl Journal {
+    /// Transactions can only be posted to active journals.
+    pub fn is_active(&self) -> bool {
+        self.status == JournalStatus::Active
+    }
+}
+
+/// Position of a journal in the listing order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: JournalId,
+}
+
+impl From<&Journal> for JournalCursor {
+    fn from(journal: &Journal) -> Self {
+        Self {
+            created_at: journal.created_at,
+            id: journal.id,
+        }
+    }
+}
+
+/// An entry in the audit trail of a journal's closed periods.
+///
+/// Each close or reopen records the date through which the journal is closed
+/// from then on, transactions effective on or before it can't be posted.
+#[derive(Debug, Clone)]
+pub struct PeriodClosing {
+    pub journal_id: JournalId,
+    pub version: u32,
+    /// `None` once every period has been reopened.
+    pub closed_through: Option<NaiveDate>,
+    /// Why the period was reopened.
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
 /// Representation of a new ledger journal entity
 /// with required/optional properties and a builder.
 #[derive(Debug, Builder)]
@@ -25,7 +65,7 @@ pub struct NewJournal {
     #[builder(setter(strip_option, into), default)]
     pub(super) description: Option<String>,
     #[builder(default)]
-    pub(super) status: Status,
+    pub(super) status: JournalStatus,
 }
 
 impl NewJournal {
@@ -45,7 +85,7 @@ mod tests {
         let new_journal = NewJournal::builder().name("name").build().unwrap();
         assert_eq!(new_journal.name, "name");
         assert_eq!(new_journal.description, None);
-        assert_eq!(new_journal.status, Status::Active);
+        assert_eq!(new_journal.status, JournalStatus::Active);
     }
 
     #[test]
@@ -1,8 +1,10 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use sqlx::{Pool, Postgres, Transaction};
 use tracing::instrument;
+use uuid::Uuid;
 
 use super::entity::*;
-use crate::{error::*, primitives::*};
+use crate::{error::*, pagination::*, primitives::*};
 
 /// Repository for working with `Journal` entities.
 #[derive(Debug, Clone)]
@@ -41,10 +43,311 @@ impl Journals {
             id as JournalId,
             name,
             description,
-            status as Status,
+            status as JournalStatus,
         )
         .fetch_one(&mut **tx)
         .await?;
         Ok(JournalId::from(record.id))
     }
+
+    #[instrument(name = "sqlx_ledger.journals.find_by_id", skip(self))]
+    pub async fn find_by_id(&self, id: JournalId) -> Result<Option<Journal>, SqlxLedgerError> {
+        let record = sqlx::query_as!(
+            JournalRow,
+            r#"SELECT id, version, name, description, status AS "status: JournalStatus", modified_at, created_at
+            FROM sqlx_ledger_journals
+            WHERE id = $1
+            ORDER BY version DESC LIMIT 1"#,
+            id as JournalId
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(record.map(Journal::from))
+    }
+
+    #[instrument(name = "sqlx_ledger.journals.find_by_name", skip(self))]
+    pub async fn find_by_name(&self, name: &str) -> Result<Option<Journal>, SqlxLedgerError> {
+        let record = sqlx::query_as!(
+            JournalRow,
+            r#"SELECT id, version, name, description, status AS "status: JournalStatus", modified_at, created_at
+            FROM sqlx_ledger_journals
+            WHERE name = $1
+            ORDER BY version DESC LIMIT 1"#,
+            name
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(record.map(Journal::from))
+    }
+
+    /// Lists the latest version of all journals, ordered by creation.
+    #[instrument(name = "sqlx_ledger.journals.list", skip(self))]
+    pub async fn list(
+        &self,
+        query: PaginatedQueryArgs<JournalCursor>,
+    ) -> Result<PaginatedQueryRet<Journal, JournalCursor>, SqlxLedgerError> {
+        let (after_created_at, after_id) = match query.after {
+            Some(JournalCursor { created_at, id }) => (Some(created_at), Some(id)),
+            None => (None, None),
+        };
+        let records = sqlx::query_as!(
+            JournalRow,
+            r#"SELECT id, version, name, description, status AS "status: JournalStatus", modified_at, created_at
+            FROM sqlx_ledger_journals j
+            WHERE version = (SELECT MAX(version) FROM sqlx_ledger_journals WHERE id = j.id)
+            AND ($1::TIMESTAMPTZ IS NULL OR (created_at, id) > ($1, $2))
+            ORDER BY created_at, id
+            LIMIT $3"#,
+            after_created_at,
+            after_id as Option<JournalId>,
+            (query.first + 1) as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let has_next_page = records.len() > query.first;
+        let entities: Vec<_> = records
+            .into_iter()
+            .take(query.first)
+            .map(Journal::from)
+            .collect();
+        Ok(PaginatedQueryRet {
+            end_cursor: entities.last().map(JournalCursor::from),
+            entities,
+            has_next_page,
+        })
+    }
+
+    /// Writes a new version of the journal with the given description.
+    #[instrument(name = "sqlx_ledger.journals.update", skip(self))]
+    pub async fn update(
+        &self,
+        id: JournalId,
+        description: Option<String>,
+    ) -> Result<JournalId, SqlxLedgerError> {
+        self.insert_version(id, description, None).await
+    }
+
+    /// Writes a new version of the journal with the given status,
+    /// transactions can only be posted to [JournalStatus::Active] journals.
+    #[instrument(name = "sqlx_ledger.journals.update_status", skip(self))]
+    pub async fn update_status(
+        &self,
+        id: JournalId,
+        status: JournalStatus,
+    ) -> Result<JournalId, SqlxLedgerError> {
+        self.insert_version(id, None, Some(status)).await
+    }
+
+    async fn insert_version(
+        &self,
+        id: JournalId,
+        description: Option<String>,
+        status: Option<JournalStatus>,
+    ) -> Result<JournalId, SqlxLedgerError> {
+        let mut tx = self.pool.begin().await?;
+        Self::lock_in_tx(&mut tx, id).await?;
+        let result = sqlx::query_file!(
+            "src/journal/sql/update-journal.sql",
+            id as JournalId,
+            description,
+            status as Option<JournalStatus>
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(SqlxLedgerError::JournalNotFound(id));
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Closes every period of the journal up to and including `up_to_date`.
+    /// Closing never moves backwards, use [reopen_period](Self::reopen_period) for that.
+    #[instrument(name = "sqlx_ledger.journals.close_period", skip(self))]
+    pub async fn close_period(
+        &self,
+        journal_id: JournalId,
+        up_to_date: NaiveDate,
+    ) -> Result<PeriodClosing, SqlxLedgerError> {
+        let mut tx = self.pool.begin().await?;
+        Self::lock_in_tx(&mut tx, journal_id).await?;
+        let record = sqlx::query_as!(
+            PeriodClosingRow,
+            r#"INSERT INTO sqlx_ledger_periods (journal_id, version, closed_through)
+            SELECT $1, COALESCE(MAX(version), 0) + 1,
+              GREATEST($2, (SELECT closed_through FROM sqlx_ledger_periods WHERE journal_id = $1 ORDER BY version DESC LIMIT 1))
+            FROM sqlx_ledger_periods WHERE journal_id = $1
+            RETURNING journal_id, version, closed_through, reason, created_at"#,
+            journal_id as JournalId,
+            up_to_date
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(PeriodClosing::from(record))
+    }
+
+    /// Reopens the periods of the journal from `from_date` onwards,
+    /// the `reason` is kept in the audit trail.
+    /// Reopening a journal that was never closed leaves it open (`closed_through` is `None`).
+    #[instrument(name = "sqlx_ledger.journals.reopen_period", skip(self))]
+    pub async fn reopen_period(
+        &self,
+        journal_id: JournalId,
+        from_date: NaiveDate,
+        reason: impl Into<String> + std::fmt::Debug,
+    ) -> Result<PeriodClosing, SqlxLedgerError> {
+        let mut tx = self.pool.begin().await?;
+        Self::lock_in_tx(&mut tx, journal_id).await?;
+        let record = sqlx::query_as!(
+            PeriodClosingRow,
+            r#"INSERT INTO sqlx_ledger_periods (journal_id, version, closed_through, reason)
+            SELECT $1, COALESCE(MAX(version), 0) + 1,
+              (SELECT CASE WHEN closed_through IS NOT NULL THEN LEAST($2::DATE - 1, closed_through) END
+               FROM sqlx_ledger_periods WHERE journal_id = $1 ORDER BY version DESC LIMIT 1),
+              $3
+            FROM sqlx_ledger_periods WHERE journal_id = $1
+            RETURNING journal_id, version, closed_through, reason, created_at"#,
+            journal_id as JournalId,
+            from_date,
+            reason.into()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(PeriodClosing::from(record))
+    }
+
+    /// Every close and reopen of the journal's periods, oldest first.
+    #[instrument(name = "sqlx_ledger.journals.period_history", skip(self))]
+    pub async fn period_history(
+        &self,
+        journal_id: JournalId,
+    ) -> Result<Vec<PeriodClosing>, SqlxLedgerError> {
+        let records = sqlx::query_as!(
+            PeriodClosingRow,
+            r#"SELECT journal_id, version, closed_through, reason, created_at
+            FROM sqlx_ledger_periods
+            WHERE journal_id = $1
+            ORDER BY version"#,
+            journal_id as JournalId
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(records.into_iter().map(PeriodClosing::from).collect())
+    }
+
+    /// Fails with [SqlxLedgerError::JournalNotActive] unless the journal accepts transactions
+    /// and with [SqlxLedgerError::PeriodClosed] if `effective` falls in a closed period.
+    /// The journal stays locked against status changes and period closings until `tx` completes.
+    #[instrument(
+        level = "trace",
+        name = "sqlx_ledger.journals.ensure_postable_in_tx",
+        skip(self, tx)
+    )]
+    pub(crate) async fn ensure_postable_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: JournalId,
+        effective: NaiveDate,
+    ) -> Result<(), SqlxLedgerError> {
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock_shared(hashtextextended($1::UUID::TEXT, 0))",
+            id as JournalId
+        )
+        .execute(&mut **tx)
+        .await?;
+        let record = sqlx::query!(
+            r#"SELECT j.status AS "status: JournalStatus", p.closed_through
+            FROM sqlx_ledger_journals j
+            LEFT JOIN LATERAL (
+              SELECT closed_through
+              FROM sqlx_ledger_periods
+              WHERE journal_id = j.id
+              ORDER BY version DESC LIMIT 1
+            ) p ON TRUE
+            WHERE j.id = $1
+            ORDER BY j.version DESC LIMIT 1"#,
+            id as JournalId
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(SqlxLedgerError::JournalNotFound(id))?;
+        if record.status != JournalStatus::Active {
+            return Err(SqlxLedgerError::JournalNotActive(id, record.status));
+        }
+        match record.closed_through {
+            Some(closed_through) if effective <= closed_through => {
+                Err(SqlxLedgerError::PeriodClosed(id, closed_through))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Journal versions and period closings are inserted rather than updated in place,
+    // so there is no single row to lock. Writers take an exclusive advisory lock on the
+    // journal id instead, posting takes it shared.
+    pub(crate) async fn lock_in_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: JournalId,
+    ) -> Result<(), SqlxLedgerError> {
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended($1::UUID::TEXT, 0))",
+            id as JournalId
+        )
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query!(
+            "SELECT id FROM sqlx_ledger_journals WHERE id = $1 AND version = 1",
+            id as JournalId
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(SqlxLedgerError::JournalNotFound(id))?;
+        Ok(())
+    }
+}
+
+struct JournalRow {
+    id: Uuid,
+    version: i32,
+    name: String,
+    description: Option<String>,
+    status: JournalStatus,
+    modified_at: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<JournalRow> for Journal {
+    fn from(row: JournalRow) -> Self {
+        Journal {
+            id: JournalId::from(row.id),
+            name: row.name,
+            description: row.description,
+            status: row.status,
+            version: row.version as u32,
+            modified_at: row.modified_at,
+            created_at: row.created_at,
+        }
+    }
+}
+
+struct PeriodClosingRow {
+    journal_id: Uuid,
+    version: i32,
+    closed_through: Option<NaiveDate>,
+    reason: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<PeriodClosingRow> for PeriodClosing {
+    fn from(row: PeriodClosingRow) -> Self {
+        PeriodClosing {
+            journal_id: JournalId::from(row.journal_id),
+            version: row.version as u32,
+            closed_through: row.closed_through,
+            reason: row.reason,
+            created_at: row.created_at,
+        }
+    }
 }
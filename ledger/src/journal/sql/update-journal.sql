@@ -0,0 +1,6 @@
+INSERT INTO sqlx_ledger_journals
+  (id, version, name, description, status, created_at)
+(
+ SELECT id, version + 1, name, COALESCE($2, description), COALESCE($3, status), created_at
+ FROM sqlx_ledger_journals WHERE id = $1 ORDER BY version DESC LIMIT 1
+)
@@ -0,0 +1,317 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use tracing::instrument;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use super::SqlxLedger;
+use crate::{
+    account::{AccountCursor, AccountFilter},
+    entry::*,
+    error::*,
+    journal::Journals,
+    pagination::*,
+    primitives::*,
+    tx_template::*,
+};
+
+/// Code of the template the closing transactions are posted with.
+pub const CLOSE_INCOME_ACCOUNTS_TX_TEMPLATE_CODE: &str = "SQLX_LEDGER_CLOSE_INCOME_ACCOUNTS";
+
+const CLOSE_INCOME_ACCOUNT: &str = "CLOSE_INCOME_ACCOUNT";
+const CLOSE_RETAINED_EARNINGS: &str = "CLOSE_RETAINED_EARNINGS";
+
+/// The transactions posted by [SqlxLedger::close_income_accounts].
+#[derive(Debug, Clone)]
+pub struct IncomeClose {
+    /// Shared by the closing transactions, pass it to
+    /// [SqlxLedger::reverse_income_close] to undo the close.
+    pub correlation_id: CorrelationId,
+    /// One per currency with a balance to close.
+    pub transaction_ids: Vec<TransactionId>,
+}
+
+impl SqlxLedger {
+    /// Zeroes the settled balance, as of `as_of`, of every account matching
+    /// `account_selector` into `retained_earnings_account`.
+    ///
+    /// One balanced transaction effective `as_of` is posted per currency.
+    /// Posting to the journal waits until the close completes, so the balances
+    /// closed are the ones the close transactions offset.
+    #[instrument(name = "sqlx_ledger.close_income_accounts", skip(self))]
+    pub async fn close_income_accounts(
+        &self,
+        journal_id: JournalId,
+        as_of: NaiveDate,
+        retained_earnings_account: AccountId,
+        account_selector: AccountFilter,
+    ) -> Result<IncomeClose, SqlxLedgerError> {
+        let tx_template = self.close_income_accounts_tx_template().await?;
+        let functions = self.functions.load(&tx_template).await?;
+        let correlation_id = CorrelationId::new();
+
+        let mut tx = self.pool.begin().await?;
+        Journals::lock_in_tx(&mut tx, journal_id).await?;
+        self.journals
+            .ensure_postable_in_tx(&mut tx, journal_id, as_of)
+            .await?;
+        let account_ids = self
+            .selected_account_ids_in_tx(&mut tx, account_selector, retained_earnings_account)
+            .await?;
+        let totals = self
+            .entries
+            .settled_totals_in_tx(&mut tx, journal_id, &account_ids, as_of)
+            .await?;
+        let mut by_currency: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+        for total in totals.iter() {
+            by_currency
+                .entry(total.currency.code())
+                .or_default()
+                .push(total);
+        }
+
+        let mut transaction_ids = Vec::new();
+        for totals in by_currency.into_values() {
+            let currency = totals[0].currency;
+            let mut new_entries = Vec::new();
+            let mut retained = Decimal::ZERO;
+            for total in totals {
+                let net = total.dr_amount - total.cr_amount;
+                if net == Decimal::ZERO {
+                    continue;
+                }
+                retained += net;
+                new_entries.push(closing_entry(
+                    total.account_id,
+                    CLOSE_INCOME_ACCOUNT,
+                    currency,
+                    -net,
+                ));
+            }
+            if new_entries.is_empty() {
+                continue;
+            }
+            if retained != Decimal::ZERO {
+                new_entries.push(closing_entry(
+                    retained_earnings_account,
+                    CLOSE_RETAINED_EARNINGS,
+                    currency,
+                    retained,
+                ));
+            }
+
+            let mut params = TxParams::new();
+            params.insert("journal_id", journal_id);
+            params.insert("effective", as_of);
+            params.insert("correlation_id", correlation_id);
+            params.insert(
+                "description",
+                format!("Close income accounts in {} as of {as_of}", currency.code()),
+            );
+            params.insert(
+                "metadata",
+                json!({
+                    "close_income_accounts": {
+                        "as_of": as_of,
+                        "retained_earnings_account_id": retained_earnings_account,
+                    }
+                }),
+            );
+            let (new_tx, mut entries) = tx_template.prep_tx(params, &functions, &self.clock)?;
+            entries.extend(new_entries);
+            let tx_id = TransactionId::new();
+            self.post_prepared_in_tx(&mut tx, tx_id, new_tx, entries)
+                .await?;
+            transaction_ids.push(tx_id);
+        }
+        tx.commit().await?;
+
+        Ok(IncomeClose {
+            correlation_id,
+            transaction_ids,
+        })
+    }
+
+    /// Posts the mirror image of every transaction of an income close,
+    /// effective on the same date, restoring the closed balances.
+    #[instrument(name = "sqlx_ledger.reverse_income_close", skip(self))]
+    pub async fn reverse_income_close(
+        &self,
+        correlation_id: CorrelationId,
+    ) -> Result<Vec<TransactionId>, SqlxLedgerError> {
+        let tx_template = self.close_income_accounts_tx_template().await?;
+        let functions = self.functions.load(&tx_template).await?;
+
+        let mut tx = self.pool.begin().await?;
+        self.transactions
+            .lock_correlation_in_tx(&mut tx, correlation_id)
+            .await?;
+        let (reversals, transactions): (Vec<_>, Vec<_>) = self
+            .transactions
+            .list_by_correlation_id_in_tx(&mut tx, correlation_id)
+            .await?
+            .into_iter()
+            .filter(|tx| tx.tx_template_id == tx_template.id())
+            .partition(|tx| {
+                tx.metadata_json
+                    .as_ref()
+                    .is_some_and(|m| m.get("reverses").is_some())
+            });
+        if transactions.is_empty() || !reversals.is_empty() {
+            return Err(SqlxLedgerError::NoIncomeCloseToReverse(correlation_id));
+        }
+        let mut entries = self
+            .entries
+            .list_by_transaction_ids_in_tx(&mut tx, transactions.iter().map(|tx| tx.id))
+            .await?;
+
+        let mut transaction_ids = Vec::new();
+        for closing in transactions {
+            let mut params = TxParams::new();
+            params.insert("journal_id", closing.journal_id);
+            params.insert("effective", closing.effective);
+            params.insert("correlation_id", correlation_id);
+            params.insert(
+                "description",
+                format!(
+                    "Reverse {}",
+                    closing.description.as_deref().unwrap_or("income close")
+                ),
+            );
+            params.insert("metadata", json!({ "reverses": closing.id }));
+            let (new_tx, mut new_entries) = tx_template.prep_tx(params, &functions, &self.clock)?;
+            for entry in entries.remove(&closing.id).unwrap_or_default() {
+                let mut builder = NewEntry::builder();
+                builder
+                    .account_id(entry.account_id)
+                    .entry_type(entry.entry_type)
+                    .layer(entry.layer)
+                    .units(entry.units)
+                    .currency(entry.currency)
+                    .direction(match entry.direction {
+                        DebitOrCredit::Debit => DebitOrCredit::Credit,
+                        DebitOrCredit::Credit => DebitOrCredit::Debit,
+                    });
+                new_entries.push(builder.build().expect("Couldn't build entry"));
+            }
+            let tx_id = TransactionId::new();
+            self.post_prepared_in_tx(&mut tx, tx_id, new_tx, new_entries)
+                .await?;
+            transaction_ids.push(tx_id);
+        }
+        tx.commit().await?;
+
+        Ok(transaction_ids)
+    }
+
+    async fn selected_account_ids_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        filter: AccountFilter,
+        excluded: AccountId,
+    ) -> Result<Vec<AccountId>, SqlxLedgerError> {
+        let mut ids = Vec::new();
+        let mut after: Option<AccountCursor> = None;
+        loop {
+            let page = self
+                .accounts
+                .list_in_tx::<serde_json::Value>(
+                    tx,
+                    filter.clone(),
+                    PaginatedQueryArgs {
+                        after,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            ids.extend(
+                page.entities
+                    .iter()
+                    .map(|account| account.id)
+                    .filter(|id| *id != excluded),
+            );
+            if !page.has_next_page {
+                return Ok(ids);
+            }
+            after = page.end_cursor;
+        }
+    }
+
+    /// The closing entries are generated, the template only carries the
+    /// transaction level fields.
+    async fn close_income_accounts_tx_template(
+        &self,
+    ) -> Result<Arc<TxTemplateCore>, SqlxLedgerError> {
+        match self
+            .tx_templates
+            .find_core(CLOSE_INCOME_ACCOUNTS_TX_TEMPLATE_CODE)
+            .await
+        {
+            Err(SqlxLedgerError::TxTemplateNotFound(_)) => (),
+            res => return res,
+        }
+        let param = |name: &str, r#type| {
+            ParamDefinition::builder()
+                .name(name)
+                .r#type(r#type)
+                .build()
+                .expect("Couldn't build ParamDefinition")
+        };
+        let new_template = NewTxTemplate::builder()
+            .id(TxTemplateId::new())
+            .code(CLOSE_INCOME_ACCOUNTS_TX_TEMPLATE_CODE)
+            .description("Closes income accounts into retained earnings")
+            .params(vec![
+                param("journal_id", ParamDataType::UUID),
+                param("effective", ParamDataType::DATE),
+                param("correlation_id", ParamDataType::UUID),
+                param("description", ParamDataType::STRING),
+                param("metadata", ParamDataType::JSON),
+            ])
+            .tx_input(
+                TxInput::builder()
+                    .journal_id("params.journal_id")
+                    .effective("params.effective")
+                    .correlation_id("params.correlation_id")
+                    .description("params.description")
+                    .metadata("params.metadata")
+                    .build()
+                    .expect("Couldn't build TxInput"),
+            )
+            .entries(vec![])
+            .build()
+            .expect("Couldn't build NewTxTemplate");
+        match self.tx_templates.create(new_template).await {
+            // Created concurrently
+            Ok(_) | Err(SqlxLedgerError::DuplicateKey(_)) => (),
+            Err(e) => return Err(e),
+        }
+        self.tx_templates
+            .find_core(CLOSE_INCOME_ACCOUNTS_TX_TEMPLATE_CODE)
+            .await
+    }
+}
+
+/// Debits the account for positive `amount`, credits it for negative ones.
+fn closing_entry(
+    account_id: AccountId,
+    entry_type: &str,
+    currency: Currency,
+    amount: Decimal,
+) -> NewEntry {
+    let mut builder = NewEntry::builder();
+    builder
+        .account_id(account_id)
+        .entry_type(entry_type.to_string())
+        .layer(Layer::Settled)
+        .units(amount.abs())
+        .currency(currency)
+        .direction(if amount > Decimal::ZERO {
+            DebitOrCredit::Debit
+        } else {
+            DebitOrCredit::Credit
+        });
+    builder.build().expect("Couldn't build entry")
+}
@@ -0,0 +1,78 @@
+use rust_decimal::Decimal;
+
+use std::collections::HashMap;
+
+use crate::{entry::Entry, primitives::*, transaction::Transaction};
+
+/// The chain of transactions sharing a [CorrelationId], ie. the hold, settlement,
+/// fee and refund of a payment, together with their combined effect.
+#[derive(Debug, Clone)]
+pub struct CorrelationSummary {
+    pub correlation_id: CorrelationId,
+    /// Ordered by creation.
+    pub transactions: Vec<Transaction>,
+    pub entries: HashMap<TransactionId, Vec<Entry>>,
+    /// One per account, currency and layer touched by the chain, in order of appearance.
+    pub net_effects: Vec<NetEffect>,
+}
+
+/// The sum of the entries of a chain posted to one account, currency and layer.
+#[derive(Debug, Clone)]
+pub struct NetEffect {
+    pub account_id: AccountId,
+    pub currency: Currency,
+    pub layer: Layer,
+    pub dr_amount: Decimal,
+    pub cr_amount: Decimal,
+}
+
+impl NetEffect {
+    /// The net amount in the direction of the account's normal balance.
+    pub fn net(&self, normal_balance_type: DebitOrCredit) -> Decimal {
+        if normal_balance_type == DebitOrCredit::Credit {
+            self.cr_amount - self.dr_amount
+        } else {
+            self.dr_amount - self.cr_amount
+        }
+    }
+}
+
+impl CorrelationSummary {
+    pub(crate) fn new(
+        correlation_id: CorrelationId,
+        transactions: Vec<Transaction>,
+        entries: HashMap<TransactionId, Vec<Entry>>,
+    ) -> Self {
+        let mut net_effects: Vec<NetEffect> = Vec::new();
+        let mut positions = HashMap::new();
+        for entry in transactions
+            .iter()
+            .filter_map(|tx| entries.get(&tx.id))
+            .flatten()
+        {
+            let position = *positions
+                .entry((entry.account_id, entry.currency, entry.layer))
+                .or_insert_with(|| {
+                    net_effects.push(NetEffect {
+                        account_id: entry.account_id,
+                        currency: entry.currency,
+                        layer: entry.layer,
+                        dr_amount: Decimal::ZERO,
+                        cr_amount: Decimal::ZERO,
+                    });
+                    net_effects.len() - 1
+                });
+            let effect = &mut net_effects[position];
+            match entry.direction {
+                DebitOrCredit::Debit => effect.dr_amount += entry.units,
+                DebitOrCredit::Credit => effect.cr_amount += entry.units,
+            }
+        }
+        Self {
+            correlation_id,
+            transactions,
+            entries,
+            net_effects,
+        }
+    }
+}
@@ -1,3 +1,6 @@
+mod closing;
+mod correlation;
+
 use sqlx::{Acquire, PgPool, Postgres, Transaction};
 use tracing::instrument;
 
@@ -8,6 +11,9 @@ use crate::{
     transaction::*, tx_template::*,
 };
 
+pub use closing::*;
+pub use correlation::*;
+
 #[derive(Debug, Clone)]
 pub struct SqlxLedger {
     pool: PgPool,
@@ -17,6 +23,8 @@ pub struct SqlxLedger {
     transactions: Transactions,
     entries: Entries,
     balances: Balances,
+    functions: FunctionRegistry,
+    clock: CelClock,
 }
 
 impl SqlxLedger {
@@ -28,10 +36,25 @@ impl SqlxLedger {
             transactions: Transactions::new(pool),
             entries: Entries::new(pool),
             balances: Balances::new(pool),
+            functions: FunctionRegistry::default(),
+            clock: CelClock::default(),
             pool: pool.clone(),
         }
     }
 
+    /// Makes the host functions in `functions` available to all template expressions.
+    pub fn with_function_registry(mut self, functions: FunctionRegistry) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Sets the clock and timezone `date()` uses in template expressions,
+    /// ie. to evaluate `effective: "date()"` in the business' timezone.
+    pub fn with_clock(mut self, clock: CelClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     pub fn accounts(&self) -> &Accounts {
         &self.accounts
     }
@@ -77,15 +100,32 @@ impl SqlxLedger {
         params: Option<impl Into<TxParams> + std::fmt::Debug>,
     ) -> Result<(), SqlxLedgerError> {
         let tx_template = self.tx_templates.find_core(tx_template_code).await?;
-        let (new_tx, new_entries) =
-            tx_template.prep_tx(params.map(|p| p.into()).unwrap_or_default())?;
-        let (journal_id, tx_id) = self
-            .transactions
-            .create_in_tx(&mut tx, tx_id, new_tx)
+        let functions = self.functions.load(&tx_template).await?;
+        let (new_tx, new_entries) = tx_template.prep_tx(
+            params.map(|p| p.into()).unwrap_or_default(),
+            &functions,
+            &self.clock,
+        )?;
+        self.post_prepared_in_tx(&mut tx, tx_id, new_tx, new_entries)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn post_prepared_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        tx_id: TransactionId,
+        new_tx: NewTransaction,
+        new_entries: Vec<NewEntry>,
+    ) -> Result<(), SqlxLedgerError> {
+        self.journals
+            .ensure_postable_in_tx(tx, new_tx.journal_id(), new_tx.effective())
             .await?;
+        let (journal_id, tx_id) = self.transactions.create_in_tx(tx, tx_id, new_tx).await?;
         let entries = self
             .entries
-            .create_all(journal_id, tx_id, new_entries, &mut tx)
+            .create_all(journal_id, tx_id, new_entries, tx)
             .await?;
         {
             let ids: Vec<(AccountId, &Currency)> = entries
@@ -128,10 +168,31 @@ impl SqlxLedger {
                 .await?;
             balance_tx.commit().await?;
         }
-        tx.commit().await?;
         Ok(())
     }
 
+    /// Collects every transaction sharing `correlation_id` with its entries
+    /// and their net effect per account, currency and layer.
+    #[instrument(name = "sqlx_ledger.correlation_summary", skip(self))]
+    pub async fn correlation_summary(
+        &self,
+        correlation_id: CorrelationId,
+    ) -> Result<CorrelationSummary, SqlxLedgerError> {
+        let transactions = self
+            .transactions
+            .list_by_correlation_id(correlation_id)
+            .await?;
+        let entries = self
+            .entries
+            .list_by_transaction_ids(transactions.iter().map(|tx| tx.id))
+            .await?;
+        Ok(CorrelationSummary::new(
+            correlation_id,
+            transactions,
+            entries,
+        ))
+    }
+
     pub async fn events(
         &self,
         opts: EventSubscriberOpts,
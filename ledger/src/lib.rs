@@ -159,8 +159,10 @@ pub mod tx_template;
 mod error;
 mod ledger;
 mod macros;
+mod pagination;
 mod primitives;
 
 pub use error::*;
 pub use ledger::*;
+pub use pagination::*;
 pub use primitives::*;
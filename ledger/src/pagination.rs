@@ -0,0 +1,26 @@
+/// Arguments of a keyset paginated query.
+#[derive(Debug, Clone)]
+pub struct PaginatedQueryArgs<C> {
+    /// Maximum number of entities to return.
+    pub first: usize,
+    /// Return entities following this cursor, `None` starts from the beginning.
+    pub after: Option<C>,
+}
+
+impl<C> Default for PaginatedQueryArgs<C> {
+    fn default() -> Self {
+        Self {
+            first: 100,
+            after: None,
+        }
+    }
+}
+
+/// A page of entities returned by a keyset paginated query.
+#[derive(Debug, Clone)]
+pub struct PaginatedQueryRet<T, C> {
+    pub entities: Vec<T>,
+    pub has_next_page: bool,
+    /// Cursor of the last entity, to be passed as `after` to fetch the next page.
+    pub end_cursor: Option<C>,
+}
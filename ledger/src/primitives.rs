@@ -11,7 +11,7 @@ crate::entity_id! { EntryId }
 crate::entity_id! { TxTemplateId }
 crate::entity_id! { CorrelationId }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
 #[sqlx(type_name = "Layer", rename_all = "snake_case")]
 pub enum Layer {
     Settled,
@@ -24,18 +24,31 @@ impl<'a> TryFrom<CelResult<'a>> for Layer {
 
     fn try_from(CelResult { val, .. }: CelResult) -> Result<Self, Self::Error> {
         match val {
-            CelValue::String(v) if v.as_ref() == "SETTLED" => Ok(Layer::Settled),
-            CelValue::String(v) if v.as_ref() == "PENDING" => Ok(Layer::Pending),
-            CelValue::String(v) if v.as_ref() == "ENCUMBERED" => Ok(Layer::Encumbered),
+            CelValue::String(v) => v.parse(),
             v => Err(SqlxLedgerError::UnknownLayer(format!("{v:?}"))),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+impl std::str::FromStr for Layer {
+    type Err = SqlxLedgerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SETTLED" => Ok(Layer::Settled),
+            "PENDING" => Ok(Layer::Pending),
+            "ENCUMBERED" => Ok(Layer::Encumbered),
+            _ => Err(SqlxLedgerError::UnknownLayer(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(type_name = "DebitOrCredit", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum DebitOrCredit {
     Debit,
+    #[default]
     Credit,
 }
 
@@ -44,28 +57,79 @@ impl<'a> TryFrom<CelResult<'a>> for DebitOrCredit {
 
     fn try_from(CelResult { val, .. }: CelResult) -> Result<Self, Self::Error> {
         match val {
-            CelValue::String(v) if v.as_ref() == "DEBIT" => Ok(DebitOrCredit::Debit),
-            CelValue::String(v) if v.as_ref() == "CREDIT" => Ok(DebitOrCredit::Credit),
+            CelValue::String(v) => v.parse(),
             v => Err(SqlxLedgerError::UnknownDebitOrCredit(format!("{v:?}"))),
         }
     }
 }
 
-impl Default for DebitOrCredit {
-    fn default() -> Self {
-        Self::Credit
+impl std::str::FromStr for DebitOrCredit {
+    type Err = SqlxLedgerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DEBIT" => Ok(DebitOrCredit::Debit),
+            "CREDIT" => Ok(DebitOrCredit::Credit),
+            _ => Err(SqlxLedgerError::UnknownDebitOrCredit(s.to_string())),
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(type_name = "Status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum Status {
+    #[default]
     Active,
 }
 
-impl Default for Status {
-    fn default() -> Self {
-        Self::Active
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "JournalStatus", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    #[default]
+    Active,
+    /// No new transactions can be posted.
+    Locked,
+    /// Retired, no new transactions can be posted.
+    Archived,
+}
+
+/// Classification of an account in the financial statements.
+///
+/// Contra accounts offset the balance of their parent category and so carry
+/// the opposite normal balance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "AccountCategory", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AccountCategory {
+    Asset,
+    ContraAsset,
+    Liability,
+    ContraLiability,
+    Equity,
+    ContraEquity,
+    Revenue,
+    ContraRevenue,
+    Expense,
+    ContraExpense,
+}
+
+impl AccountCategory {
+    /// The side increasing the balance of accounts in this category.
+    pub fn normal_balance_type(&self) -> DebitOrCredit {
+        match self {
+            AccountCategory::Asset
+            | AccountCategory::Expense
+            | AccountCategory::ContraLiability
+            | AccountCategory::ContraEquity
+            | AccountCategory::ContraRevenue => DebitOrCredit::Debit,
+            AccountCategory::Liability
+            | AccountCategory::Equity
+            | AccountCategory::Revenue
+            | AccountCategory::ContraAsset
+            | AccountCategory::ContraExpense => DebitOrCredit::Credit,
+        }
     }
 }
 
@@ -84,6 +148,14 @@ impl Currency {
             Currency::Crypto(c) => c.code,
         }
     }
+
+    /// Number of decimal places of the currency's minor unit.
+    pub fn exponent(&self) -> u32 {
+        match self {
+            Currency::Iso(c) => c.exponent,
+            Currency::Crypto(c) => c.exponent,
+        }
+    }
 }
 
 impl std::fmt::Display for Currency {
@@ -28,6 +28,41 @@ impl Transaction {
     }
 }
 
+/// Criteria for listing transactions, unset fields match every transaction.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    pub journal_id: Option<JournalId>,
+    /// Earliest effective date, inclusive.
+    pub effective_from: Option<NaiveDate>,
+    /// Latest effective date, inclusive.
+    pub effective_to: Option<NaiveDate>,
+    /// Created at or after this time.
+    pub created_from: Option<DateTime<Utc>>,
+    /// Created before this time.
+    pub created_until: Option<DateTime<Utc>>,
+    pub correlation_id: Option<CorrelationId>,
+    /// Code of the [TxTemplate](crate::tx_template::TxTemplate) the transaction was posted with.
+    pub tx_template_code: Option<String>,
+    /// Matches transactions whose metadata contains this JSON (`@>`).
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Position of a transaction in the listing order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: TransactionId,
+}
+
+impl From<&Transaction> for TransactionCursor {
+    fn from(transaction: &Transaction) -> Self {
+        Self {
+            created_at: transaction.created_at,
+            id: transaction.id,
+        }
+    }
+}
+
 #[derive(Builder)]
 pub(crate) struct NewTransaction {
     #[builder(setter(into))]
@@ -48,4 +83,12 @@ impl NewTransaction {
     pub fn builder() -> NewTransactionBuilder {
         NewTransactionBuilder::default()
     }
+
+    pub(crate) fn journal_id(&self) -> JournalId {
+        self.journal_id
+    }
+
+    pub(crate) fn effective(&self) -> NaiveDate {
+        self.effective
+    }
 }
@@ -1,9 +1,11 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
 use sqlx::{Pool, Postgres, Transaction as DbTransaction};
 use tracing::instrument;
 use uuid::Uuid;
 
 use super::entity::*;
-use crate::{error::*, primitives::*};
+use crate::{error::*, pagination::*, primitives::*};
 
 /// Repository for working with `TxTemplate` entities.
 #[derive(Debug, Clone)]
@@ -49,34 +51,187 @@ impl Transactions {
         Ok((journal_id, TransactionId::from(record.id)))
     }
 
+    /// Writes a new version of the transaction with the given description and
+    /// metadata, fields that are `None` keep their value. Entries are not affected.
+    #[instrument(name = "sqlx_ledger.transactions.update", skip(self))]
+    pub async fn update<T: Serialize + std::fmt::Debug>(
+        &self,
+        id: TransactionId,
+        description: Option<String>,
+        metadata: Option<T>,
+    ) -> Result<TransactionId, SqlxLedgerError> {
+        self.insert_version(id, None, description, metadata).await
+    }
+
+    /// Like [update](Self::update) but only succeeds if `expected_version` is
+    /// still the latest version of the transaction, otherwise the update fails with
+    /// [SqlxLedgerError::OptimisticLockingError].
+    #[instrument(name = "sqlx_ledger.transactions.update_with_version", skip(self))]
+    pub async fn update_with_version<T: Serialize + std::fmt::Debug>(
+        &self,
+        id: TransactionId,
+        expected_version: u32,
+        description: Option<String>,
+        metadata: Option<T>,
+    ) -> Result<TransactionId, SqlxLedgerError> {
+        self.insert_version(id, Some(expected_version), description, metadata)
+            .await
+            .map_err(|e| match e {
+                // A newer version makes the insert collide with it on (id, version)
+                SqlxLedgerError::DuplicateKey(_) => SqlxLedgerError::OptimisticLockingError,
+                e => e,
+            })
+    }
+
+    async fn insert_version<T: Serialize>(
+        &self,
+        id: TransactionId,
+        expected_version: Option<u32>,
+        description: Option<String>,
+        metadata: Option<T>,
+    ) -> Result<TransactionId, SqlxLedgerError> {
+        let metadata_json = match metadata {
+            Some(m) => Some(serde_json::to_value(m)?),
+            None => None,
+        };
+        let result = sqlx::query_file!(
+            "src/transaction/sql/update-transaction.sql",
+            id as TransactionId,
+            expected_version.map(|v| v as i32),
+            description,
+            metadata_json
+        )
+        .execute(&self.pool)
+        .await?;
+        match (result.rows_affected(), expected_version) {
+            (0, Some(_)) => Err(SqlxLedgerError::OptimisticLockingError),
+            (0, None) => Err(SqlxLedgerError::TransactionNotFound(id)),
+            _ => Ok(id),
+        }
+    }
+
+    /// Lists the latest version of the transactions matching `filter`,
+    /// ordered by creation.
+    #[instrument(name = "sqlx_ledger.transactions.list", skip(self))]
+    pub async fn list(
+        &self,
+        filter: TransactionFilter,
+        query: PaginatedQueryArgs<TransactionCursor>,
+    ) -> Result<PaginatedQueryRet<Transaction, TransactionCursor>, SqlxLedgerError> {
+        let (after_created_at, after_id) = match query.after {
+            Some(TransactionCursor { created_at, id }) => (Some(created_at), Some(id)),
+            None => (None, None),
+        };
+        let records = sqlx::query_as!(
+            TransactionRow,
+            r#"SELECT id, version, journal_id, tx_template_id, effective, correlation_id, external_id, description, metadata, created_at, modified_at
+            FROM sqlx_ledger_transactions t
+            WHERE version = (SELECT MAX(version) FROM sqlx_ledger_transactions WHERE id = t.id)
+            AND ($1::UUID IS NULL OR journal_id = $1)
+            AND ($2::DATE IS NULL OR effective >= $2)
+            AND ($3::DATE IS NULL OR effective <= $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR created_at >= $4)
+            AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+            AND ($6::UUID IS NULL OR correlation_id = $6)
+            AND ($7::VARCHAR IS NULL OR tx_template_id IN (SELECT id FROM sqlx_ledger_tx_templates WHERE code = $7))
+            AND ($8::JSONB IS NULL OR metadata @> $8)
+            AND ($9::TIMESTAMPTZ IS NULL OR (created_at, id) > ($9, $10))
+            ORDER BY created_at, id
+            LIMIT $11"#,
+            filter.journal_id as Option<JournalId>,
+            filter.effective_from,
+            filter.effective_to,
+            filter.created_from,
+            filter.created_until,
+            filter.correlation_id as Option<CorrelationId>,
+            filter.tx_template_code,
+            filter.metadata,
+            after_created_at,
+            after_id as Option<TransactionId>,
+            (query.first + 1) as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let has_next_page = records.len() > query.first;
+        let entities: Vec<_> = records
+            .into_iter()
+            .take(query.first)
+            .map(Transaction::from)
+            .collect();
+        Ok(PaginatedQueryRet {
+            end_cursor: entities.last().map(TransactionCursor::from),
+            entities,
+            has_next_page,
+        })
+    }
+
+    /// Lists the latest version of every transaction sharing `correlation_id`,
+    /// ordered by creation.
+    pub async fn list_by_correlation_id(
+        &self,
+        correlation_id: CorrelationId,
+    ) -> Result<Vec<Transaction>, SqlxLedgerError> {
+        let mut tx = self.pool.begin().await?;
+        let res = self
+            .list_by_correlation_id_in_tx(&mut tx, correlation_id)
+            .await?;
+        tx.commit().await?;
+        Ok(res)
+    }
+
+    #[instrument(
+        name = "sqlx_ledger.transactions.list_by_correlation_id",
+        skip(self, tx)
+    )]
+    pub async fn list_by_correlation_id_in_tx(
+        &self,
+        tx: &mut DbTransaction<'_, Postgres>,
+        correlation_id: CorrelationId,
+    ) -> Result<Vec<Transaction>, SqlxLedgerError> {
+        let records = sqlx::query_as!(
+            TransactionRow,
+            r#"SELECT id, version, journal_id, tx_template_id, effective, correlation_id, external_id, description, metadata, created_at, modified_at
+            FROM sqlx_ledger_transactions t
+            WHERE correlation_id = $1
+            AND version = (SELECT MAX(version) FROM sqlx_ledger_transactions WHERE id = t.id)
+            ORDER BY created_at, id"#,
+            correlation_id as CorrelationId
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        Ok(records.into_iter().map(Transaction::from).collect())
+    }
+
+    /// Serializes writers of the chain sharing `correlation_id` until `tx` completes.
+    pub(crate) async fn lock_correlation_in_tx(
+        &self,
+        tx: &mut DbTransaction<'_, Postgres>,
+        correlation_id: CorrelationId,
+    ) -> Result<(), SqlxLedgerError> {
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended($1::UUID::TEXT, 0))",
+            correlation_id as CorrelationId
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
     pub async fn list_by_external_ids(
         &self,
         ids: Vec<String>,
     ) -> Result<Vec<Transaction>, SqlxLedgerError> {
-        let records = sqlx::query!(
+        let records = sqlx::query_as!(
+            TransactionRow,
             r#"SELECT id, version, journal_id, tx_template_id, effective, correlation_id, external_id, description, metadata, created_at, modified_at
-            FROM sqlx_ledger_transactions
-            WHERE external_id = ANY($1)"#,
+            FROM sqlx_ledger_transactions t
+            WHERE external_id = ANY($1)
+            AND version = (SELECT MAX(version) FROM sqlx_ledger_transactions WHERE id = t.id)"#,
             &ids[..]
         )
         .fetch_all(&self.pool)
         .await?;
-        Ok(records
-            .into_iter()
-            .map(|row| Transaction {
-                id: TransactionId::from(row.id),
-                version: row.version as u32,
-                journal_id: JournalId::from(row.journal_id),
-                tx_template_id: TxTemplateId::from(row.tx_template_id),
-                effective: row.effective,
-                correlation_id: CorrelationId::from(row.correlation_id),
-                external_id: row.external_id,
-                description: row.description,
-                metadata_json: row.metadata,
-                created_at: row.created_at,
-                modified_at: row.modified_at,
-            })
-            .collect())
+        Ok(records.into_iter().map(Transaction::from).collect())
     }
 
     pub async fn list_by_ids(
@@ -84,59 +239,65 @@ impl Transactions {
         ids: impl IntoIterator<Item = impl std::borrow::Borrow<TransactionId>>,
     ) -> Result<Vec<Transaction>, SqlxLedgerError> {
         let ids: Vec<_> = ids.into_iter().map(|id| Uuid::from(id.borrow())).collect();
-        let records = sqlx::query!(
+        let records = sqlx::query_as!(
+            TransactionRow,
             r#"SELECT id, version, journal_id, tx_template_id, effective, correlation_id, external_id, description, metadata, created_at, modified_at
-            FROM sqlx_ledger_transactions
-            WHERE id = ANY($1)"#,
+            FROM sqlx_ledger_transactions t
+            WHERE id = ANY($1)
+            AND version = (SELECT MAX(version) FROM sqlx_ledger_transactions WHERE id = t.id)"#,
             &ids[..]
         )
         .fetch_all(&self.pool)
         .await?;
-        Ok(records
-            .into_iter()
-            .map(|row| Transaction {
-                id: TransactionId::from(row.id),
-                version: row.version as u32,
-                journal_id: JournalId::from(row.journal_id),
-                tx_template_id: TxTemplateId::from(row.tx_template_id),
-                effective: row.effective,
-                correlation_id: CorrelationId::from(row.correlation_id),
-                external_id: row.external_id,
-                description: row.description,
-                metadata_json: row.metadata,
-                created_at: row.created_at,
-                modified_at: row.modified_at,
-            })
-            .collect())
+        Ok(records.into_iter().map(Transaction::from).collect())
     }
 
     pub async fn list_by_template_id(
         &self,
         id: TxTemplateId,
     ) -> Result<Vec<Transaction>, SqlxLedgerError> {
-        let records = sqlx::query!(
+        let records = sqlx::query_as!(
+            TransactionRow,
             r#"SELECT id, version, journal_id, tx_template_id, effective, correlation_id, external_id, description, metadata, created_at, modified_at
-            FROM sqlx_ledger_transactions
-            WHERE tx_template_id = $1"#,
+            FROM sqlx_ledger_transactions t
+            WHERE tx_template_id = $1
+            AND version = (SELECT MAX(version) FROM sqlx_ledger_transactions WHERE id = t.id)"#,
             id as TxTemplateId
         )
         .fetch_all(&self.pool)
         .await?;
-        Ok(records
-            .into_iter()
-            .map(|row| Transaction {
-                id: TransactionId::from(row.id),
-                version: row.version as u32,
-                journal_id: JournalId::from(row.journal_id),
-                tx_template_id: TxTemplateId::from(row.tx_template_id),
-                effective: row.effective,
-                correlation_id: CorrelationId::from(row.correlation_id),
-                external_id: row.external_id,
-                description: row.description,
-                metadata_json: row.metadata,
-                created_at: row.created_at,
-                modified_at: row.modified_at,
-            })
-            .collect())
+        Ok(records.into_iter().map(Transaction::from).collect())
+    }
+}
+
+struct TransactionRow {
+    id: Uuid,
+    version: i32,
+    journal_id: Uuid,
+    tx_template_id: Uuid,
+    effective: NaiveDate,
+    correlation_id: Uuid,
+    external_id: String,
+    description: Option<String>,
+    metadata: Option<serde_json::Value>,
+    created_at: DateTime<Utc>,
+    modified_at: DateTime<Utc>,
+}
+
+impl From<TransactionRow> for Transaction {
+    fn from(row: TransactionRow) -> Self {
+        Transaction {
+            id: TransactionId::from(row.id),
+            version: row.version as u32,
+            journal_id: JournalId::from(row.journal_id),
+            tx_template_id: TxTemplateId::from(row.tx_template_id),
+            effective: row.effective,
+            correlation_id: CorrelationId::from(row.correlation_id),
+            external_id: row.external_id,
+            description: row.description,
+            metadata_json: row.metadata,
+            created_at: row.created_at,
+            modified_at: row.modified_at,
+        }
     }
 }
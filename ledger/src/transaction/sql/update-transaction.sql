@@ -0,0 +1,7 @@
+INSERT INTO sqlx_ledger_transactions
+  (id, version, journal_id, tx_template_id, correlation_id, effective, external_id, description, metadata, created_at)
+(
+ SELECT id, version + 1, journal_id, tx_template_id, correlation_id, effective, external_id, COALESCE($3, description), COALESCE($4, metadata), created_at
+ FROM sqlx_ledger_transactions WHERE id = $1 AND ($2::INT IS NULL OR version = $2)
+ ORDER BY version DESC LIMIT 1
+)
@@ -1,4 +1,8 @@
-use cel_interpreter::CelContext;
+use cel_interpreter::{CelClock, CelContext, CelError, CelType, CelTypeEnv, CelValue};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use super::{function_registry::FunctionRegistry, param_definition::ParamDefinition};
+use crate::primitives::Currency;
 
 pub(super) fn initialize() -> CelContext {
     let mut ctx = CelContext::new();
@@ -7,5 +11,105 @@ pub(super) fn initialize() -> CelContext {
     ctx.add_variable("ENCUMBERED", "ENCUMBERED");
     ctx.add_variable("DEBIT", "DEBIT");
     ctx.add_variable("CREDIT", "CREDIT");
+    ctx.add_function("round_to_currency", round_to_currency);
+    ctx
+}
+
+pub(super) fn initialize_with(functions: &FunctionRegistry, clock: &CelClock) -> CelContext {
+    let mut ctx = initialize();
+    ctx.set_clock(clock.clone());
+    functions.register(&mut ctx);
     ctx
 }
+
+pub(super) fn type_env(
+    params: Option<&Vec<ParamDefinition>>,
+    functions: &FunctionRegistry,
+) -> CelTypeEnv {
+    let mut env = CelTypeEnv::new();
+    for name in ["SETTLED", "PENDING", "ENCUMBERED", "DEBIT", "CREDIT"] {
+        env.add_variable(name, Some(CelType::String));
+    }
+    env.add_function("round_to_currency", Some(CelType::Decimal));
+    functions.declare(&mut env);
+    if let Some(params) = params {
+        env.add_record(
+            "params",
+            params.iter().map(|p| (p.name.clone(), p.r#type.cel_type())),
+        );
+    }
+    env
+}
+
+/// `round_to_currency(amount, 'USD')` rounds half-even to the currency's minor unit.
+fn round_to_currency(args: Vec<CelValue>) -> Result<CelValue, CelError> {
+    let amount = match args.first() {
+        Some(CelValue::Decimal(d)) => *d,
+        Some(CelValue::Int(i)) => Decimal::from(*i),
+        Some(CelValue::UInt(u)) => Decimal::from(*u),
+        Some(v) => {
+            return Err(CelError::WrongArgumentType(
+                CelType::Decimal,
+                CelType::from(v),
+            ))
+        }
+        None => return Err(CelError::MissingArgument),
+    };
+    let currency: Currency = match args.get(1) {
+        Some(CelValue::String(code)) => code
+            .parse()
+            .map_err(|_| CelError::UnknownCurrency(code.to_string()))?,
+        Some(v) => {
+            return Err(CelError::WrongArgumentType(
+                CelType::String,
+                CelType::from(v),
+            ))
+        }
+        None => return Err(CelError::MissingArgument),
+    };
+    Ok(CelValue::Decimal(amount.round_dp_with_strategy(
+        currency.exponent(),
+        RoundingStrategy::MidpointNearestEven,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cel_interpreter::CelExpression;
+
+    fn eval(source: &str) -> CelValue {
+        source
+            .parse::<CelExpression>()
+            .unwrap()
+            .evaluate(&initialize())
+            .unwrap()
+    }
+
+    #[test]
+    fn rounds_to_currency_minor_units() {
+        assert_eq!(
+            eval("round_to_currency(10.125m, 'USD')"),
+            CelValue::Decimal("10.12".parse().unwrap())
+        );
+        assert_eq!(
+            eval("round_to_currency(10.5m, 'JPY')"),
+            CelValue::Decimal("10".parse().unwrap())
+        );
+        assert_eq!(
+            eval("round_to_currency(0.123456789m, 'BTC')"),
+            CelValue::Decimal("0.12345679".parse().unwrap())
+        );
+        let err = "round_to_currency(1m, 'XYZ')"
+            .parse::<CelExpression>()
+            .unwrap()
+            .evaluate(&initialize())
+            .unwrap_err();
+        match err {
+            CelError::EvaluationError(_, e) => {
+                assert!(matches!(*e, CelError::UnknownCurrency(code) if code == "XYZ"))
+            }
+            e => panic!("unexpected error {e}"),
+        }
+    }
+}
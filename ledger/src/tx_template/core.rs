@@ -4,12 +4,14 @@ use serde::Deserialize;
 use tracing::instrument;
 use uuid::Uuid;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{entry::*, error::*, primitives::*, transaction::NewTransaction};
-use cel_interpreter::{CelContext, CelExpression};
+use cel_interpreter::{CelClock, CelContext, CelError, CelExpression, CelResult};
 
-use super::{param_definition::ParamDefinition, tx_params::TxParams};
+use super::{
+    function_registry::FunctionRegistry, param_definition::ParamDefinition, tx_params::TxParams,
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct TxInputCel {
@@ -32,49 +34,98 @@ pub(crate) struct EntryCel {
     description: Option<CelExpression>,
 }
 
+/// A template ready for posting. Its expressions are compiled into evaluation
+/// plans once when the template is loaded and reused for every transaction.
 #[derive(Debug, Clone)]
 pub(crate) struct TxTemplateCore {
     pub(super) id: TxTemplateId,
-    pub(super) _code: String,
+    pub(super) code: String,
     pub(super) params: Option<Vec<ParamDefinition>>,
     pub(super) tx_input: TxInputCel,
     pub(super) entries: Vec<EntryCel>,
 }
 
 impl TxTemplateCore {
+    pub(crate) fn id(&self) -> TxTemplateId {
+        self.id
+    }
+
+    /// Names of the variables and functions the template's expressions,
+    /// including the param defaults, refer to.
+    pub(super) fn identifiers(&self) -> HashSet<String> {
+        let tx_input = &self.tx_input;
+        let mut identifiers: HashSet<String> = [&tx_input.effective, &tx_input.journal_id]
+            .into_iter()
+            .chain(tx_input.correlation_id.as_ref())
+            .chain(tx_input.external_id.as_ref())
+            .chain(tx_input.description.as_ref())
+            .chain(tx_input.metadata.as_ref())
+            .chain(self.entries.iter().flat_map(|entry| {
+                [
+                    &entry.entry_type,
+                    &entry.account_id,
+                    &entry.layer,
+                    &entry.direction,
+                    &entry.units,
+                    &entry.currency,
+                ]
+                .into_iter()
+                .chain(entry.description.as_ref())
+            }))
+            .flat_map(CelExpression::identifiers)
+            .map(str::to_string)
+            .collect();
+        for default in self
+            .params
+            .iter()
+            .flatten()
+            .filter_map(ParamDefinition::default_expr)
+        {
+            identifiers.extend(default.identifiers().map(str::to_string));
+        }
+        identifiers
+    }
+
     #[instrument(level = "trace", name = "sqlx_ledger.tx_template_core.prep_tx")]
     pub(crate) fn prep_tx(
         &self,
         params: TxParams,
+        functions: &FunctionRegistry,
+        clock: &CelClock,
     ) -> Result<(NewTransaction, Vec<NewEntry>), SqlxLedgerError> {
         let mut tx_builder = NewTransaction::builder();
         tx_builder.tx_template_id(self.id);
 
-        let ctx = params.to_context(self.params.as_ref())?;
+        let ctx = params
+            .into_context(self.params.as_ref(), functions, clock)
+            .map_err(|e| self.evaluation_error("params".to_string(), e))?;
 
-        let journal_id: Uuid = self.tx_input.journal_id.try_evaluate(&ctx)?;
+        let journal_id: Uuid =
+            self.evaluate(&self.tx_input.journal_id, &ctx, "journal_id", None)?;
         tx_builder.journal_id(journal_id);
 
-        let effective: NaiveDate = self.tx_input.effective.try_evaluate(&ctx)?;
+        let effective: NaiveDate =
+            self.evaluate(&self.tx_input.effective, &ctx, "effective", None)?;
         tx_builder.effective(effective);
 
         if let Some(correlation_id) = self.tx_input.correlation_id.as_ref() {
-            let correlation_id: Uuid = correlation_id.try_evaluate(&ctx)?;
+            let correlation_id: Uuid =
+                self.evaluate(correlation_id, &ctx, "correlation_id", None)?;
             tx_builder.correlation_id(correlation_id.into());
         }
 
         if let Some(external_id) = self.tx_input.external_id.as_ref() {
-            let external_id: String = external_id.try_evaluate(&ctx)?;
+            let external_id: String = self.evaluate(external_id, &ctx, "external_id", None)?;
             tx_builder.external_id(external_id);
         }
 
         if let Some(description) = self.tx_input.description.as_ref() {
-            let description: String = description.try_evaluate(&ctx)?;
+            let description: String = self.evaluate(description, &ctx, "description", None)?;
             tx_builder.description(description);
         }
 
         if let Some(metadata) = self.tx_input.metadata.as_ref() {
-            let metadata: serde_json::Value = metadata.try_evaluate(&ctx)?;
+            let metadata: serde_json::Value = self.evaluate(metadata, &ctx, "metadata", None)?;
             tx_builder.metadata(metadata);
         }
 
@@ -87,20 +138,23 @@ impl TxTemplateCore {
     fn prep_entries(&self, ctx: CelContext) -> Result<Vec<NewEntry>, SqlxLedgerError> {
         let mut new_entries = Vec::new();
         let mut totals = HashMap::new();
-        for entry in self.entries.iter() {
+        for (idx, entry) in self.entries.iter().enumerate() {
             let mut builder = NewEntry::builder();
-            let account_id: Uuid = entry.account_id.try_evaluate(&ctx)?;
+            let account_id: Uuid =
+                self.evaluate(&entry.account_id, &ctx, "account_id", Some(idx))?;
             builder.account_id(account_id.into());
 
-            let entry_type: String = entry.entry_type.try_evaluate(&ctx)?;
+            let entry_type: String =
+                self.evaluate(&entry.entry_type, &ctx, "entry_type", Some(idx))?;
             builder.entry_type(entry_type);
 
-            let layer: Layer = entry.layer.try_evaluate(&ctx)?;
+            let layer: Layer = self.evaluate(&entry.layer, &ctx, "layer", Some(idx))?;
             builder.layer(layer);
 
-            let units: Decimal = entry.units.try_evaluate(&ctx)?;
-            let currency: Currency = entry.currency.try_evaluate(&ctx)?;
-            let direction: DebitOrCredit = entry.direction.try_evaluate(&ctx)?;
+            let units: Decimal = self.evaluate(&entry.units, &ctx, "units", Some(idx))?;
+            let currency: Currency = self.evaluate(&entry.currency, &ctx, "currency", Some(idx))?;
+            let direction: DebitOrCredit =
+                self.evaluate(&entry.direction, &ctx, "direction", Some(idx))?;
 
             let total = totals.entry(currency).or_insert(Decimal::ZERO);
             match direction {
@@ -112,7 +166,8 @@ impl TxTemplateCore {
             builder.direction(direction);
 
             if let Some(description) = entry.description.as_ref() {
-                let description: String = description.try_evaluate(&ctx)?;
+                let description: String =
+                    self.evaluate(description, &ctx, "description", Some(idx))?;
                 builder.description(description);
             }
 
@@ -127,4 +182,29 @@ impl TxTemplateCore {
 
         Ok(new_entries)
     }
+
+    fn evaluate<'a, T, E>(
+        &self,
+        expr: &'a CelExpression,
+        ctx: &CelContext,
+        field: &str,
+        entry: Option<usize>,
+    ) -> Result<T, SqlxLedgerError>
+    where
+        T: TryFrom<CelResult<'a>, Error = E>,
+        E: From<CelError>,
+        SqlxLedgerError: From<E>,
+    {
+        expr.try_evaluate(ctx).map_err(|e| {
+            let field = match entry {
+                Some(idx) => format!("entries[{idx}].{field}"),
+                None => field.to_string(),
+            };
+            self.evaluation_error(field, SqlxLedgerError::from(e))
+        })
+    }
+
+    fn evaluation_error(&self, field: String, e: SqlxLedgerError) -> SqlxLedgerError {
+        SqlxLedgerError::TxTemplateEvaluation(self.code.clone(), field, Box::new(e))
+    }
 }
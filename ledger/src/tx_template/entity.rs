@@ -1,9 +1,11 @@
 use derive_builder::Builder;
 use serde::Serialize;
 
-use cel_interpreter::CelExpression;
+use std::collections::HashSet;
 
-use super::param_definition::*;
+use cel_interpreter::{CelExpression, CelType, CelValue};
+
+use super::{function_registry::FunctionRegistry, param_definition::*};
 use crate::primitives::*;
 
 /// Representation of a new TxTemplate created via a builder.
@@ -11,7 +13,12 @@ use crate::primitives::*;
 /// TxTemplate is an entity that takes a set of params including
 /// a `TxInput` entity and a set of `EntryInput` entities. It can
 /// later be used to create a `Transaction`.
+///
+/// Building fails if an expression references an undeclared param or function
+/// or doesn't evaluate to the type its field requires. Templates calling functions
+/// of a [FunctionRegistry] must be built with it, see [NewTxTemplateBuilder::function_registry].
 #[derive(Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct NewTxTemplate {
     #[builder(setter(into))]
     pub(super) id: TxTemplateId,
@@ -25,12 +32,25 @@ pub struct NewTxTemplate {
     pub(super) entries: Vec<EntryInput>,
     #[builder(setter(custom), default)]
     pub(super) metadata: Option<serde_json::Value>,
+    #[builder(setter(custom), default)]
+    pub(super) functions: FunctionRegistry,
 }
 
 impl NewTxTemplate {
     pub fn builder() -> NewTxTemplateBuilder {
         NewTxTemplateBuilder::default()
     }
+
+    /// Non fatal issues found while checking the template (ie. unused params).
+    pub fn warnings(&self) -> Vec<String> {
+        check_template(
+            self.params.as_ref(),
+            &self.tx_input,
+            &self.entries,
+            &self.functions,
+        )
+        .unwrap_or_default()
+    }
 }
 
 impl NewTxTemplateBuilder {
@@ -41,6 +61,23 @@ impl NewTxTemplateBuilder {
         self.metadata = Some(Some(serde_json::to_value(metadata)?));
         Ok(self)
     }
+
+    /// Type checks calls to the functions and messages of `functions`.
+    pub fn function_registry(&mut self, functions: &FunctionRegistry) -> &mut Self {
+        self.functions = Some(functions.clone());
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let (Some(tx_input), Some(entries)) = (self.tx_input.as_ref(), self.entries.as_ref()) {
+            let params = self.params.as_ref().and_then(|p| p.as_ref());
+            let functions = self.functions.clone().unwrap_or_default();
+            for warning in check_template(params, tx_input, entries, &functions)? {
+                tracing::warn!("{warning}");
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Contains the transaction-level details needed to create a `Transaction`.
@@ -147,6 +184,94 @@ impl EntryInputBuilder {
     }
 }
 
+fn check_template(
+    params: Option<&Vec<ParamDefinition>>,
+    tx_input: &TxInput,
+    entries: &[EntryInput],
+    functions: &FunctionRegistry,
+) -> Result<Vec<String>, String> {
+    let env = super::cel_context::type_env(params, functions);
+    let mut used_params = HashSet::new();
+    let mut check = |field: &str, expr: &str, expected: Option<CelType>| {
+        let expr = CelExpression::try_from(expr).map_err(|e| format!("{field}: {e}"))?;
+        let res = expr.check(&env).map_err(|e| format!("{field}: {e}"))?;
+        if let (Some(expected), Some(found)) = (expected, res.result_type) {
+            if expected != found {
+                return Err(format!(
+                    "{field}: expected {expected:?} found {found:?} in '{expr}'"
+                ));
+            }
+        }
+        used_params.extend(res.field_references.into_iter().map(|(_, name)| name));
+        Ok(expr)
+    };
+
+    for param in params.into_iter().flatten() {
+        if let Some(expr) = param.default.as_ref() {
+            check(
+                &format!("params.{}.default", param.name),
+                expr,
+                param.r#type.cel_type(),
+            )?;
+        }
+    }
+    check("effective", &tx_input.effective, Some(CelType::Date))?;
+    check("journal_id", &tx_input.journal_id, Some(CelType::Uuid))?;
+    if let Some(expr) = tx_input.correlation_id.as_ref() {
+        check("correlation_id", expr, Some(CelType::Uuid))?;
+    }
+    if let Some(expr) = tx_input.external_id.as_ref() {
+        check("external_id", expr, Some(CelType::String))?;
+    }
+    if let Some(expr) = tx_input.description.as_ref() {
+        check("description", expr, Some(CelType::String))?;
+    }
+    if let Some(expr) = tx_input.metadata.as_ref() {
+        check("metadata", expr, None)?;
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        let field = |name: &str| format!("entries[{i}].{name}");
+        check(
+            &field("entry_type"),
+            &entry.entry_type,
+            Some(CelType::String),
+        )?;
+        check(&field("account_id"), &entry.account_id, Some(CelType::Uuid))?;
+        let layer = check(&field("layer"), &entry.layer, Some(CelType::String))?;
+        if let Some(layer) = constant_string(&layer) {
+            layer
+                .parse::<Layer>()
+                .map_err(|e| format!("{}: {e}", field("layer")))?;
+        }
+        let direction = check(&field("direction"), &entry.direction, Some(CelType::String))?;
+        if let Some(direction) = constant_string(&direction) {
+            direction
+                .parse::<DebitOrCredit>()
+                .map_err(|e| format!("{}: {e}", field("direction")))?;
+        }
+        check(&field("units"), &entry.units, Some(CelType::Decimal))?;
+        check(&field("currency"), &entry.currency, Some(CelType::String))?;
+        if let Some(expr) = entry.description.as_ref() {
+            check(&field("description"), expr, Some(CelType::String))?;
+        }
+    }
+
+    Ok(params
+        .into_iter()
+        .flatten()
+        .filter(|p| !used_params.contains(&p.name))
+        .map(|p| format!("Param '{}' is never used", p.name))
+        .collect())
+}
+
+/// Evaluates expressions that don't depend on params so literals can be validated up front.
+fn constant_string(expr: &CelExpression) -> Option<String> {
+    match expr.evaluate(&super::cel_context::initialize()) {
+        Ok(CelValue::String(s)) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
 fn validate_expression(expr: &str) -> Result<(), String> {
     CelExpression::try_from(expr).map_err(|e| e.to_string())?;
     Ok(())
@@ -163,32 +288,45 @@ mod tests {
     use super::*;
     use uuid::Uuid;
 
-    #[test]
-    fn it_builds() {
-        let journal_id = Uuid::new_v4();
-        let entries = vec![EntryInput::builder()
+    fn entry() -> EntryInputBuilder {
+        let mut builder = EntryInput::builder();
+        builder
             .entry_type("'TEST_DR'")
-            .account_id("param.recipient")
-            .layer("'Settled'")
-            .direction("'Settled'")
-            .units("1290")
-            .currency("'BTC'")
+            .account_id("params.recipient")
+            .layer("SETTLED")
+            .direction("DEBIT")
+            .units("decimal('1290')")
+            .currency("'BTC'");
+        builder
+    }
+
+    fn template(entry: EntryInput) -> Result<NewTxTemplate, NewTxTemplateBuilderError> {
+        let journal_id = Uuid::new_v4();
+        let params = vec![ParamDefinition::builder()
+            .name("recipient")
+            .r#type(ParamDataType::UUID)
             .build()
             .unwrap()];
-        let new_journal = NewTxTemplate::builder()
+        NewTxTemplate::builder()
             .id(Uuid::new_v4())
             .code("CODE")
+            .params(params)
             .tx_input(
                 TxInput::builder()
                     .effective("date('2022-11-01')")
-                    .journal_id(format!("'{journal_id}'"))
+                    .journal_id(format!("uuid('{journal_id}')"))
                     .build()
                     .unwrap(),
             )
-            .entries(entries)
+            .entries(vec![entry])
             .build()
-            .unwrap();
+    }
+
+    #[test]
+    fn it_builds() {
+        let new_journal = template(entry().build().unwrap()).unwrap();
         assert_eq!(new_journal.description, None);
+        assert!(new_journal.warnings().is_empty());
     }
 
     #[test]
@@ -196,4 +334,119 @@ mod tests {
         let new_account = NewTxTemplate::builder().build();
         assert!(new_account.is_err());
     }
+
+    #[test]
+    fn rejects_mistyped_units() {
+        let res = template(entry().units("'1290'").build().unwrap());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn accepts_units_promoted_to_decimal() {
+        for units in ["abs(decimal('-2')) * 2", "sum([1.5m, 2m]) * 2"] {
+            assert!(template(entry().units(units).build().unwrap()).is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_mistyped_account_id() {
+        let res = template(entry().account_id("'recipient'").build().unwrap());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_layer_and_direction() {
+        let res = template(entry().layer("'Settled'").build().unwrap());
+        assert!(res.is_err());
+        let res = template(entry().direction("'Settled'").build().unwrap());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn rejects_undeclared_params() {
+        let res = template(entry().account_id("params.sender").build().unwrap());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn warns_about_unused_params() {
+        let new_template = template(
+            entry()
+                .account_id(format!("uuid('{}')", Uuid::new_v4()))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            new_template.warnings(),
+            vec!["Param 'recipient' is never used".to_string()]
+        );
+    }
+
+    #[test]
+    fn checks_calls_to_registered_functions() {
+        let res = template(entry().units("fee_schedule('gold')").build().unwrap());
+        assert!(res.is_err());
+
+        let mut functions = FunctionRegistry::new();
+        functions
+            .add_function("fee_schedule", |_| Ok(CelValue::Null))
+            .return_type("fee_schedule", CelType::Decimal)
+            .add_function("label", |_| Ok(CelValue::Null))
+            .return_type("label", CelType::String);
+        let build = |units: &str| {
+            let mut builder = NewTxTemplate::builder();
+            builder
+                .id(Uuid::new_v4())
+                .code("CODE")
+                .tx_input(
+                    TxInput::builder()
+                        .effective("date('2022-11-01')")
+                        .journal_id(format!("uuid('{}')", Uuid::new_v4()))
+                        .build()
+                        .unwrap(),
+                )
+                .entries(vec![entry()
+                    .account_id(format!("uuid('{}')", Uuid::new_v4()))
+                    .units(units)
+                    .build()
+                    .unwrap()])
+                .function_registry(&functions)
+                .build()
+        };
+        assert!(build("fee_schedule('gold') * 2").is_ok());
+        assert!(build("label('gold')").is_err());
+        assert!(build("fee_schedul('gold')").is_err());
+    }
+
+    #[test]
+    fn checks_param_defaults() {
+        let build = |default: &str| {
+            let params = vec![ParamDefinition::builder()
+                .name("fee")
+                .r#type(ParamDataType::DECIMAL)
+                .default_expr(default)
+                .build()
+                .unwrap()];
+            NewTxTemplate::builder()
+                .id(Uuid::new_v4())
+                .code("CODE")
+                .params(params)
+                .tx_input(
+                    TxInput::builder()
+                        .effective("date('2022-11-01')")
+                        .journal_id(format!("uuid('{}')", Uuid::new_v4()))
+                        .build()
+                        .unwrap(),
+                )
+                .entries(vec![entry()
+                    .account_id(format!("uuid('{}')", Uuid::new_v4()))
+                    .units("params.fee")
+                    .build()
+                    .unwrap()])
+                .build()
+        };
+        assert!(build("decimal('1')").is_ok());
+        assert!(build("base_fee()").is_err());
+    }
 }
@@ -0,0 +1,126 @@
+use cel_interpreter::{CelContext, CelError, CelFunction, CelType, CelTypeEnv, CelValue};
+
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use super::core::TxTemplateCore;
+use crate::error::SqlxLedgerError;
+
+type FunctionLoader = Arc<
+    dyn Fn() -> Pin<Box<dyn Future<Output = Result<CelFunction, CelError>> + Send>> + Send + Sync,
+>;
+
+/// Host functions and message types made available to every template expression.
+///
+/// Install it via [SqlxLedger::with_function_registry](crate::SqlxLedger::with_function_registry).
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, CelFunction>,
+    loaders: HashMap<String, FunctionLoader>,
+    return_types: HashMap<String, CelType>,
+    message_types: HashMap<String, Vec<(String, CelType)>>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_function(
+        &mut self,
+        name: impl Into<String>,
+        f: impl Fn(Vec<CelValue>) -> Result<CelValue, CelError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.functions.insert(name.into(), Arc::new(f));
+        self
+    }
+
+    /// Declares the type a registered function returns so templates calling it
+    /// are type checked when built. Without it the result is only checked when posting.
+    pub fn return_type(&mut self, name: impl Into<String>, return_type: CelType) -> &mut Self {
+        self.return_types.insert(name.into(), return_type);
+        self
+    }
+
+    /// Registers the fields of a message type so expressions like
+    /// `Metadata{order_id: params.order}` are validated when evaluated.
+    pub fn add_message_type(
+        &mut self,
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = (impl Into<String>, CelType)>,
+    ) -> &mut Self {
+        self.message_types.insert(
+            name.into(),
+            fields.into_iter().map(|(n, t)| (n.into(), t)).collect(),
+        );
+        self
+    }
+
+    /// Registers a function whose implementation is fetched asynchronously
+    /// (ie. a rate table loaded from a remote service) before the template is evaluated.
+    /// The loader only runs for templates whose expressions call the function.
+    pub fn add_async_function<L, Fut, F>(&mut self, name: impl Into<String>, loader: L) -> &mut Self
+    where
+        L: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<F, CelError>> + Send + 'static,
+        F: Fn(Vec<CelValue>) -> Result<CelValue, CelError> + Send + Sync + 'static,
+    {
+        let loader: FunctionLoader = Arc::new(move || {
+            let fut = loader();
+            Box::pin(async move { Ok(Arc::new(fut.await?) as CelFunction) })
+        });
+        self.loaders.insert(name.into(), loader);
+        self
+    }
+
+    /// Runs the loaders of the async functions `tx_template` refers to.
+    pub(crate) async fn load(
+        &self,
+        tx_template: &TxTemplateCore,
+    ) -> Result<FunctionRegistry, SqlxLedgerError> {
+        let mut functions = self.functions.clone();
+        if !self.loaders.is_empty() {
+            let identifiers = tx_template.identifiers();
+            for (name, loader) in self.loaders.iter() {
+                if identifiers.contains(name.as_str()) {
+                    functions.insert(name.clone(), loader().await?);
+                }
+            }
+        }
+        Ok(FunctionRegistry {
+            functions,
+            loaders: HashMap::new(),
+            return_types: self.return_types.clone(),
+            message_types: self.message_types.clone(),
+        })
+    }
+
+    pub(super) fn declare(&self, env: &mut CelTypeEnv) {
+        for name in self.functions.keys().chain(self.loaders.keys()) {
+            env.add_function(name.clone(), self.return_types.get(name).copied());
+        }
+        for (name, fields) in self.message_types.iter() {
+            env.add_message_type(name.clone(), fields.iter().cloned());
+        }
+    }
+
+    pub(super) fn register(&self, ctx: &mut CelContext) {
+        for (name, f) in self.functions.iter() {
+            let f = Arc::clone(f);
+            ctx.add_function(name.clone(), move |args| f(args));
+        }
+        for (name, fields) in self.message_types.iter() {
+            ctx.add_message_type(name.clone(), fields.iter().cloned());
+        }
+    }
+}
+
+impl std::fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FunctionRegistry")
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .field("loaders", &self.loaders.keys().collect::<Vec<_>>())
+            .field("return_types", &self.return_types)
+            .field("message_types", &self.message_types)
+            .finish()
+    }
+}
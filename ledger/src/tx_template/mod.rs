@@ -2,11 +2,17 @@
 mod cel_context;
 mod core;
 mod entity;
+mod function_registry;
 mod param_definition;
 mod repo;
 mod tx_params;
 
 pub use entity::*;
+pub use function_registry::*;
 pub use param_definition::*;
 pub use repo::*;
 pub use tx_params::*;
+
+pub(crate) use self::core::TxTemplateCore;
+
+pub use cel_interpreter::{CelClock, CelError, CelType, CelValue, Tz};
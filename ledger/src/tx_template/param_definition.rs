@@ -1,4 +1,4 @@
-use cel_interpreter::{CelExpression, CelType, CelValue};
+use cel_interpreter::{CelError, CelExpression, CelType, CelValue};
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
@@ -31,11 +31,15 @@ impl ParamDefinitionBuilder {
     fn validate(&self) -> Result<(), String> {
         if let Some(Some(expr)) = self.default.as_ref() {
             let expr = CelExpression::try_from(expr.as_str()).map_err(|e| e.to_string())?;
-            let param_type = ParamDataType::try_from(
-                &expr
-                    .evaluate(&super::cel_context::initialize())
-                    .map_err(|e| format!("{e}"))?,
-            )?;
+            let value = match expr.evaluate(&super::cel_context::initialize()) {
+                Ok(value) => value,
+                // Calls a function from a FunctionRegistry, it is only available when posting
+                Err(CelError::EvaluationError(_, e)) if matches!(*e, CelError::UnknownIdent(_)) => {
+                    return Ok(())
+                }
+                Err(e) => return Err(format!("{e}")),
+            };
+            let param_type = ParamDataType::try_from(&value)?;
             let specified_type = self.r#type.as_ref().unwrap();
             if &param_type != specified_type {
                 return Err(format!(
@@ -59,6 +63,20 @@ pub enum ParamDataType {
     JSON,
 }
 
+impl ParamDataType {
+    pub(super) fn cel_type(&self) -> Option<CelType> {
+        match self {
+            ParamDataType::STRING => Some(CelType::String),
+            ParamDataType::INTEGER => Some(CelType::Int),
+            ParamDataType::DECIMAL => Some(CelType::Decimal),
+            ParamDataType::BOOLEAN => Some(CelType::Bool),
+            ParamDataType::UUID => Some(CelType::Uuid),
+            ParamDataType::DATE => Some(CelType::Date),
+            ParamDataType::TIMESTAMP | ParamDataType::JSON => None,
+        }
+    }
+}
+
 impl TryFrom<&CelValue> for ParamDataType {
     type Error = String;
 
@@ -67,7 +85,7 @@ impl TryFrom<&CelValue> for ParamDataType {
         match CelType::from(value) {
             Int => Ok(ParamDataType::INTEGER),
             String => Ok(ParamDataType::STRING),
-            Map => Ok(ParamDataType::JSON),
+            Map | List => Ok(ParamDataType::JSON),
             Date => Ok(ParamDataType::DATE),
             Uuid => Ok(ParamDataType::UUID),
             Decimal => Ok(ParamDataType::DECIMAL),
@@ -91,4 +109,20 @@ mod tests {
             .unwrap();
         assert_eq!(definition.name, "name");
     }
+
+    #[test]
+    fn defers_defaults_calling_registered_functions() {
+        let definition = ParamDefinition::builder()
+            .name("fee")
+            .r#type(ParamDataType::DECIMAL)
+            .default_expr("base_fee()")
+            .build();
+        assert!(definition.is_ok());
+        let definition = ParamDefinition::builder()
+            .name("fee")
+            .r#type(ParamDataType::DECIMAL)
+            .default_expr("'10'")
+            .build();
+        assert!(definition.is_err());
+    }
 }
@@ -28,6 +28,7 @@ impl TxTemplates {
             tx_input,
             entries,
             metadata,
+            ..
         }: NewTxTemplate,
     ) -> Result<TxTemplateId, SqlxLedgerError> {
         let params_json = serde_json::to_value(&params)?;
@@ -73,8 +74,9 @@ async fn cached_find_core(
             r#"SELECT id, code, params, tx_input, entries FROM sqlx_ledger_tx_templates WHERE code = $1 LIMIT 1"#,
             code
         )
-        .fetch_one(pool)
-        .await?;
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| SqlxLedgerError::TxTemplateNotFound(code.to_string()))?;
     let params = match record.params {
         Some(serde_json::Value::Null) => None,
         Some(params) => Some(serde_json::from_value(params)?),
@@ -83,7 +85,7 @@ async fn cached_find_core(
     let tx_input = serde_json::from_value(record.tx_input)?;
     Ok(Arc::new(TxTemplateCore {
         id: TxTemplateId::from(record.id),
-        _code: record.code,
+        code: record.code,
         params,
         entries: serde_json::from_value(record.entries)?,
         tx_input,
@@ -1,7 +1,10 @@
-use cel_interpreter::{CelContext, CelMap, CelValue};
+use cel_interpreter::{CelClock, CelContext, CelMap, CelValue};
 use std::collections::HashMap;
 
-use super::param_definition::{ParamDataType, ParamDefinition};
+use super::{
+    function_registry::FunctionRegistry,
+    param_definition::{ParamDataType, ParamDefinition},
+};
 use crate::error::SqlxLedgerError;
 
 #[derive(Debug)]
@@ -21,10 +24,19 @@ impl TxParams {
     }
 
     pub fn to_context(
+        self,
+        defs: Option<&Vec<ParamDefinition>>,
+    ) -> Result<CelContext, SqlxLedgerError> {
+        self.into_context(defs, &FunctionRegistry::default(), &CelClock::default())
+    }
+
+    pub(crate) fn into_context(
         mut self,
         defs: Option<&Vec<ParamDefinition>>,
+        functions: &FunctionRegistry,
+        clock: &CelClock,
     ) -> Result<CelContext, SqlxLedgerError> {
-        let mut ctx = super::cel_context::initialize();
+        let mut ctx = super::cel_context::initialize_with(functions, clock);
         if let Some(defs) = defs {
             let mut cel_map = CelMap::new();
             for d in defs {
@@ -1,7 +1,11 @@
 mod helpers;
 
 use rand::distributions::{Alphanumeric, DistString};
-use sqlx_ledger::{account::NewAccount, *};
+use sqlx_ledger::{
+    account::{AccountChange, AccountFilter, NewAccount},
+    event::*,
+    *,
+};
 
 #[tokio::test]
 async fn test_account() -> anyhow::Result<()> {
@@ -13,6 +17,7 @@ async fn test_account() -> anyhow::Result<()> {
         .id(uuid::Uuid::new_v4())
         .name(format!("Test Account {code}"))
         .code(code)
+        .category(AccountCategory::Asset)
         .build()
         .unwrap();
     let ledger = SqlxLedger::new(&pool);
@@ -23,5 +28,198 @@ async fn test_account() -> anyhow::Result<()> {
         .await
         .unwrap();
 
+    ledger
+        .accounts()
+        .update_with_version::<()>(id, 2, Some("second description".to_string()), None)
+        .await
+        .unwrap();
+    let stale = ledger
+        .accounts()
+        .update_with_version::<()>(id, 2, Some("stale description".to_string()), None)
+        .await;
+    assert!(matches!(
+        stale,
+        Err(SqlxLedgerError::OptimisticLockingError)
+    ));
+    let account = ledger
+        .accounts()
+        .find_by_id::<()>(id)
+        .await?
+        .expect("account exists");
+    assert_eq!(account.version, 3);
+    assert_eq!(account.category, Some(AccountCategory::Asset));
+    assert_eq!(account.normal_balance_type, DebitOrCredit::Debit);
+    assert_eq!(account.description.as_deref(), Some("second description"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_accounts() -> anyhow::Result<()> {
+    let pool = helpers::init_pool().await?;
+    let ledger = SqlxLedger::new(&pool);
+
+    let prefix = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let mut builder = NewAccount::builder();
+        builder
+            .id(uuid::Uuid::new_v4())
+            .name(format!("{prefix} {i}"))
+            .code(format!("{prefix}-{i}"))
+            .metadata(serde_json::json!({ "tag": prefix, "index": i }))
+            .unwrap();
+        if i == 2 {
+            builder.category(AccountCategory::Revenue);
+        }
+        let new_account = builder.build().unwrap();
+        ids.push(ledger.accounts().create(new_account).await.unwrap());
+    }
+    ledger
+        .accounts()
+        .update::<()>(ids[0], Some("updated".to_string()), None)
+        .await
+        .unwrap();
+
+    let account = ledger
+        .accounts()
+        .find_by_id::<serde_json::Value>(ids[0])
+        .await?
+        .expect("account exists");
+    assert_eq!(account.version, 2);
+    assert_eq!(account.description.as_deref(), Some("updated"));
+    assert_eq!(account.metadata.unwrap()["index"], 0);
+
+    let account = ledger
+        .accounts()
+        .find_by_code::<serde_json::Value>(&format!("{prefix}-1"))
+        .await?
+        .expect("account exists");
+    assert_eq!(account.id, ids[1]);
+
+    let accounts = ledger
+        .accounts()
+        .find_many::<serde_json::Value>(&ids[1..])
+        .await?;
+    assert_eq!(accounts.len(), 2);
+
+    let filter = AccountFilter {
+        name_prefix: Some(prefix.clone()),
+        ..Default::default()
+    };
+    let first = ledger
+        .accounts()
+        .list::<serde_json::Value>(
+            filter.clone(),
+            PaginatedQueryArgs {
+                first: 2,
+                after: None,
+            },
+        )
+        .await?;
+    assert_eq!(first.entities.len(), 2);
+    assert!(first.has_next_page);
+    assert_eq!(first.entities[0].version, 2);
+    let second = ledger
+        .accounts()
+        .list::<serde_json::Value>(
+            filter,
+            PaginatedQueryArgs {
+                first: 2,
+                after: first.end_cursor,
+            },
+        )
+        .await?;
+    assert_eq!(second.entities.len(), 1);
+    assert!(!second.has_next_page);
+    assert_eq!(second.entities[0].id, ids[2]);
+
+    let filter = AccountFilter {
+        metadata: Some(serde_json::json!({ "tag": prefix, "index": 1 })),
+        ..Default::default()
+    };
+    let page = ledger
+        .accounts()
+        .list::<serde_json::Value>(filter, PaginatedQueryArgs::default())
+        .await?;
+    assert_eq!(page.entities.len(), 1);
+    assert_eq!(page.entities[0].id, ids[1]);
+
+    let filter = AccountFilter {
+        name_prefix: Some(prefix.clone()),
+        category: Some(AccountCategory::Revenue),
+        ..Default::default()
+    };
+    let page = ledger
+        .accounts()
+        .list::<serde_json::Value>(filter, PaginatedQueryArgs::default())
+        .await?;
+    assert_eq!(page.entities.len(), 1);
+    assert_eq!(page.entities[0].id, ids[2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn account_history() -> anyhow::Result<()> {
+    let pool = helpers::init_pool().await?;
+    let ledger = SqlxLedger::new(&pool);
+    let events = ledger.events(Default::default()).await?;
+    let mut all_events = events.all().expect("event subscriber closed");
+
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_account = NewAccount::builder()
+        .id(uuid::Uuid::new_v4())
+        .name(format!("Test Account {code}"))
+        .code(code)
+        .build()
+        .unwrap();
+    let id = ledger.accounts().create(new_account).await.unwrap();
+    ledger
+        .accounts()
+        .update(id, None, Some(serde_json::json!({ "foo": "bar" })))
+        .await
+        .unwrap();
+    ledger
+        .accounts()
+        .update::<()>(id, Some("new description".to_string()), None)
+        .await
+        .unwrap();
+
+    let history = ledger.accounts().history::<serde_json::Value>(id).await?;
+    assert_eq!(history.len(), 3);
+    assert!(history[0].changes.is_empty());
+    assert_eq!(
+        history[1].changes,
+        vec![AccountChange::Metadata {
+            from: None,
+            to: Some(serde_json::json!({ "foo": "bar" }))
+        }]
+    );
+    assert_eq!(
+        history[2].changes,
+        vec![AccountChange::Description {
+            from: None,
+            to: Some("new description".to_string())
+        }]
+    );
+    assert_eq!(history[2].account.version, 3);
+
+    let mut types = Vec::new();
+    while types.len() < 3 {
+        let event = all_events.recv().await?;
+        if event.account_id() == Some(id) {
+            types.push(event.r#type);
+        }
+    }
+    assert_eq!(
+        types,
+        vec![
+            SqlxLedgerEventType::AccountCreated,
+            SqlxLedgerEventType::AccountUpdated,
+            SqlxLedgerEventType::AccountUpdated
+        ]
+    );
+
     Ok(())
 }
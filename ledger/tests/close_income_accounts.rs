@@ -0,0 +1,410 @@
+mod helpers;
+
+use rand::distributions::{Alphanumeric, DistString};
+use rust_decimal::Decimal;
+use sqlx_ledger::{account::*, journal::*, tx_template::*, *};
+
+/// A journal with cash, two income accounts and retained earnings,
+/// and a template transferring any currency between accounts.
+struct Books {
+    pool: sqlx::PgPool,
+    ledger: SqlxLedger,
+    tag: String,
+    journal_id: JournalId,
+    tx_code: String,
+    cash: AccountId,
+    revenue: AccountId,
+    expense: AccountId,
+    retained_earnings: AccountId,
+}
+
+impl Books {
+    async fn init() -> anyhow::Result<Self> {
+        let pool = helpers::init_pool().await?;
+        let ledger = SqlxLedger::new(&pool);
+
+        let tag = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let new_journal = NewJournal::builder().name(tag.clone()).build().unwrap();
+        let journal_id = ledger.journals().create(new_journal).await?;
+
+        let create_account = |name: &'static str, category: AccountCategory, income: bool| {
+            let ledger = ledger.clone();
+            let tag = tag.clone();
+            async move {
+                let new_account = NewAccount::builder()
+                    .id(uuid::Uuid::new_v4())
+                    .name(format!("{name} {tag}"))
+                    .code(format!("{name}-{tag}"))
+                    .category(category)
+                    .metadata(serde_json::json!({ "tag": tag, "income": income }))
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                ledger.accounts().create(new_account).await
+            }
+        };
+        let cash = create_account("cash", AccountCategory::Asset, false).await?;
+        let revenue = create_account("revenue", AccountCategory::Revenue, true).await?;
+        let expense = create_account("expense", AccountCategory::Expense, true).await?;
+        let retained_earnings =
+            create_account("retained-earnings", AccountCategory::Equity, false).await?;
+
+        let tx_code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let params = ["journal_id", "debit", "credit"]
+            .into_iter()
+            .map(|name| {
+                ParamDefinition::builder()
+                    .name(name)
+                    .r#type(ParamDataType::UUID)
+                    .build()
+                    .unwrap()
+            })
+            .chain([
+                ParamDefinition::builder()
+                    .name("units")
+                    .r#type(ParamDataType::DECIMAL)
+                    .build()
+                    .unwrap(),
+                ParamDefinition::builder()
+                    .name("currency")
+                    .r#type(ParamDataType::STRING)
+                    .build()
+                    .unwrap(),
+            ])
+            .collect();
+        let entry = |account: &str, direction: &str| {
+            EntryInput::builder()
+                .entry_type("'TRANSFER'")
+                .account_id(account)
+                .layer("SETTLED")
+                .direction(direction)
+                .units("params.units")
+                .currency("params.currency")
+                .build()
+                .unwrap()
+        };
+        let new_template = NewTxTemplate::builder()
+            .id(uuid::Uuid::new_v4())
+            .code(&tx_code)
+            .params(params)
+            .tx_input(
+                TxInput::builder()
+                    .effective("date('2024-06-30')")
+                    .journal_id("params.journal_id")
+                    .build()
+                    .unwrap(),
+            )
+            .entries(vec![
+                entry("params.debit", "DEBIT"),
+                entry("params.credit", "CREDIT"),
+            ])
+            .build()
+            .unwrap();
+        ledger.tx_templates().create(new_template).await?;
+
+        Ok(Self {
+            pool,
+            ledger,
+            tag,
+            journal_id,
+            tx_code,
+            cash,
+            revenue,
+            expense,
+            retained_earnings,
+        })
+    }
+
+    async fn transfer(
+        &self,
+        debit: AccountId,
+        credit: AccountId,
+        units: i64,
+        currency: &str,
+    ) -> anyhow::Result<TransactionId> {
+        let tx_id = TransactionId::new();
+        self.ledger
+            .post_transaction(
+                tx_id,
+                &self.tx_code,
+                Some(self.transfer_params(debit, credit, units, currency)),
+            )
+            .await?;
+        Ok(tx_id)
+    }
+
+    fn transfer_params(
+        &self,
+        debit: AccountId,
+        credit: AccountId,
+        units: i64,
+        currency: &str,
+    ) -> TxParams {
+        let mut params = TxParams::new();
+        params.insert("journal_id", self.journal_id);
+        params.insert("debit", debit);
+        params.insert("credit", credit);
+        params.insert("units", Decimal::from(units));
+        params.insert("currency", currency);
+        params
+    }
+
+    async fn close(&self) -> Result<IncomeClose, SqlxLedgerError> {
+        self.ledger
+            .close_income_accounts(
+                self.journal_id,
+                closing_date(),
+                self.retained_earnings,
+                AccountFilter {
+                    metadata: Some(serde_json::json!({ "tag": self.tag, "income": true })),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
+
+    async fn settled(&self, account_id: AccountId, currency: &str) -> anyhow::Result<Decimal> {
+        let balance = self
+            .ledger
+            .balances()
+            .find(self.journal_id, account_id, currency.parse()?)
+            .await?;
+        Ok(balance.map(|b| b.settled()).unwrap_or_default())
+    }
+}
+
+fn closing_date() -> chrono::NaiveDate {
+    chrono::NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+}
+
+#[tokio::test]
+async fn close_and_reverse() -> anyhow::Result<()> {
+    let books = Books::init().await?;
+    books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+    books.transfer(books.expense, books.cash, 30, "USD").await?;
+
+    let close = books.close().await?;
+    assert_eq!(close.transaction_ids.len(), 1);
+    assert_eq!(books.settled(books.revenue, "USD").await?, Decimal::ZERO);
+    assert_eq!(books.settled(books.expense, "USD").await?, Decimal::ZERO);
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::from(70)
+    );
+    assert_eq!(books.settled(books.cash, "USD").await?, Decimal::from(70));
+
+    books
+        .ledger
+        .reverse_income_close(close.correlation_id)
+        .await?;
+    assert_eq!(
+        books.settled(books.revenue, "USD").await?,
+        Decimal::from(100)
+    );
+    assert_eq!(
+        books.settled(books.expense, "USD").await?,
+        Decimal::from(30)
+    );
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::ZERO
+    );
+    assert!(matches!(
+        books
+            .ledger
+            .reverse_income_close(close.correlation_id)
+            .await,
+        Err(SqlxLedgerError::NoIncomeCloseToReverse(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_and_reverse_multiple_currencies() -> anyhow::Result<()> {
+    let books = Books::init().await?;
+    books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+    books.transfer(books.cash, books.revenue, 2, "BTC").await?;
+    books.transfer(books.expense, books.cash, 1, "BTC").await?;
+
+    let close = books.close().await?;
+    assert_eq!(close.transaction_ids.len(), 2);
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::from(100)
+    );
+    assert_eq!(
+        books.settled(books.retained_earnings, "BTC").await?,
+        Decimal::from(1)
+    );
+
+    let reversals = books
+        .ledger
+        .reverse_income_close(close.correlation_id)
+        .await?;
+    assert_eq!(reversals.len(), 2);
+    assert_eq!(
+        books.settled(books.revenue, "USD").await?,
+        Decimal::from(100)
+    );
+    assert_eq!(books.settled(books.revenue, "BTC").await?, Decimal::from(2));
+    assert_eq!(books.settled(books.expense, "BTC").await?, Decimal::from(1));
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::ZERO
+    );
+    assert_eq!(
+        books.settled(books.retained_earnings, "BTC").await?,
+        Decimal::ZERO
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reverse_rejects_other_transactions() -> anyhow::Result<()> {
+    let books = Books::init().await?;
+    let tx_id = books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+    let tx = books
+        .ledger
+        .transactions()
+        .list_by_ids([tx_id])
+        .await?
+        .remove(0);
+
+    assert!(matches!(
+        books.ledger.reverse_income_close(tx.correlation_id).await,
+        Err(SqlxLedgerError::NoIncomeCloseToReverse(id)) if id == tx.correlation_id
+    ));
+    assert_eq!(
+        books.settled(books.revenue, "USD").await?,
+        Decimal::from(100)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reverse_into_closed_period() -> anyhow::Result<()> {
+    let books = Books::init().await?;
+    books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+    let close = books.close().await?;
+    books
+        .ledger
+        .journals()
+        .close_period(books.journal_id, closing_date())
+        .await?;
+
+    assert!(matches!(
+        books.ledger.reverse_income_close(close.correlation_id).await,
+        Err(SqlxLedgerError::PeriodClosed(_, date)) if date == closing_date()
+    ));
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::from(100)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_waits_for_posts_in_flight() -> anyhow::Result<()> {
+    let books = std::sync::Arc::new(Books::init().await?);
+    books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+
+    // A post that has passed its journal checks but not committed yet
+    let mut in_flight = books.pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock_shared(hashtextextended($1::UUID::TEXT, 0))")
+        .bind(uuid::Uuid::from(books.journal_id))
+        .execute(&mut *in_flight)
+        .await?;
+    let close = tokio::spawn({
+        let books = books.clone();
+        async move { books.close().await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!close.is_finished());
+    books
+        .ledger
+        .post_transaction_in_tx(
+            in_flight,
+            TransactionId::new(),
+            &books.tx_code,
+            Some(books.transfer_params(books.cash, books.revenue, 50, "USD")),
+        )
+        .await?;
+
+    close.await??;
+    assert_eq!(books.settled(books.revenue, "USD").await?, Decimal::ZERO);
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::from(150)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_in_closed_period() -> anyhow::Result<()> {
+    let books = Books::init().await?;
+    books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+    books
+        .ledger
+        .journals()
+        .close_period(books.journal_id, closing_date())
+        .await?;
+
+    assert!(matches!(
+        books.close().await,
+        Err(SqlxLedgerError::PeriodClosed(_, date)) if date == closing_date()
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_by_category() -> anyhow::Result<()> {
+    let books = Books::init().await?;
+    books
+        .transfer(books.cash, books.revenue, 100, "USD")
+        .await?;
+    books.transfer(books.expense, books.cash, 30, "USD").await?;
+
+    // Other revenue accounts have no entries in this journal
+    let close = books
+        .ledger
+        .close_income_accounts(
+            books.journal_id,
+            closing_date(),
+            books.retained_earnings,
+            AccountFilter {
+                category: Some(AccountCategory::Revenue),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(close.transaction_ids.len(), 1);
+    assert_eq!(books.settled(books.revenue, "USD").await?, Decimal::ZERO);
+    assert_eq!(
+        books.settled(books.expense, "USD").await?,
+        Decimal::from(30)
+    );
+    assert_eq!(
+        books.settled(books.retained_earnings, "USD").await?,
+        Decimal::from(100)
+    );
+
+    Ok(())
+}
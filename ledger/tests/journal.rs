@@ -0,0 +1,131 @@
+mod helpers;
+
+use rand::distributions::{Alphanumeric, DistString};
+use sqlx_ledger::{journal::*, *};
+
+#[tokio::test]
+async fn test_journal() -> anyhow::Result<()> {
+    let pool = helpers::init_pool().await?;
+    let ledger = SqlxLedger::new(&pool);
+
+    let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_journal = NewJournal::builder().name(name.clone()).build().unwrap();
+    let id = ledger.journals().create(new_journal).await?;
+
+    ledger
+        .journals()
+        .update(id, Some("new description".to_string()))
+        .await?;
+    ledger
+        .journals()
+        .update_status(id, JournalStatus::Locked)
+        .await?;
+
+    let journal = ledger
+        .journals()
+        .find_by_id(id)
+        .await?
+        .expect("journal exists");
+    assert_eq!(journal.version, 3);
+    assert_eq!(journal.description.as_deref(), Some("new description"));
+    assert_eq!(journal.status, JournalStatus::Locked);
+    assert!(!journal.is_active());
+
+    let journal = ledger
+        .journals()
+        .find_by_name(&name)
+        .await?
+        .expect("journal exists");
+    assert_eq!(journal.id, id);
+
+    let page = ledger
+        .journals()
+        .list(PaginatedQueryArgs {
+            first: 1,
+            after: Some(JournalCursor {
+                created_at: journal.created_at - chrono::Duration::microseconds(1),
+                id,
+            }),
+        })
+        .await?;
+    assert_eq!(page.entities.len(), 1);
+    assert_eq!(page.entities[0].version, 3);
+
+    let missing_id = JournalId::new();
+    let result = ledger
+        .journals()
+        .update_status(missing_id, JournalStatus::Archived)
+        .await;
+    assert!(matches!(
+        result,
+        Err(SqlxLedgerError::JournalNotFound(id)) if id == missing_id
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_and_reopen_periods() -> anyhow::Result<()> {
+    let pool = helpers::init_pool().await?;
+    let ledger = SqlxLedger::new(&pool);
+
+    let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_journal = NewJournal::builder().name(name).build().unwrap();
+    let id = ledger.journals().create(new_journal).await?;
+
+    let january = chrono::NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+    let february = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+    ledger.journals().close_period(id, february).await?;
+    let closing = ledger.journals().close_period(id, january).await?;
+    assert_eq!(closing.closed_through, Some(february));
+
+    let reopened = ledger
+        .journals()
+        .reopen_period(id, february, "missing invoice")
+        .await?;
+    assert_eq!(reopened.closed_through, february.pred_opt());
+
+    let history = ledger.journals().period_history(id).await?;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].reason.as_deref(), Some("missing invoice"));
+    assert_eq!(history[2].version, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reopen_never_closed_period() -> anyhow::Result<()> {
+    let pool = helpers::init_pool().await?;
+    let ledger = SqlxLedger::new(&pool);
+
+    let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_journal = NewJournal::builder().name(name).build().unwrap();
+    let id = ledger.journals().create(new_journal).await?;
+
+    let february = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+    let reopened = ledger
+        .journals()
+        .reopen_period(id, february, "nothing to reopen")
+        .await?;
+    assert_eq!(reopened.closed_through, None);
+
+    let closing = ledger.journals().close_period(id, february).await?;
+    assert_eq!(closing.closed_through, Some(february));
+    ledger
+        .journals()
+        .reopen_period(id, february, "missing invoice")
+        .await?;
+    let reopened = ledger
+        .journals()
+        .reopen_period(id, february, "still missing")
+        .await?;
+    assert_eq!(reopened.closed_through, february.pred_opt());
+
+    let result = ledger
+        .journals()
+        .close_period(JournalId::new(), february)
+        .await;
+    assert!(matches!(result, Err(SqlxLedgerError::JournalNotFound(_))));
+
+    Ok(())
+}
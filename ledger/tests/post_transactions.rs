@@ -3,119 +3,172 @@ mod helpers;
 use rust_decimal::Decimal;
 
 use rand::distributions::{Alphanumeric, DistString};
-use sqlx_ledger::{account::*, balance::AccountBalance, event::*, journal::*, tx_template::*, *};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
 
-#[tokio::test]
-async fn post_transaction() -> anyhow::Result<()> {
-    let pool = helpers::init_pool().await?;
+use sqlx_ledger::{
+    account::*, balance::AccountBalance, event::*, journal::*, transaction::*, tx_template::*, *,
+};
 
-    let tx_code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+/// A journal with two accounts and a template transferring BTC and USD between them.
+struct Transfer {
+    ledger: SqlxLedger,
+    journal_id: JournalId,
+    sender_account_id: AccountId,
+    recipient_account_id: AccountId,
+    tx_code: String,
+}
 
-    let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
-    let new_journal = NewJournal::builder().name(name).build().unwrap();
-    let ledger = SqlxLedger::new(&pool);
+impl Transfer {
+    async fn init() -> anyhow::Result<Self> {
+        let pool = helpers::init_pool().await?;
 
-    let journal_id = ledger.journals().create(new_journal).await.unwrap();
-    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
-    let new_account = NewAccount::builder()
-        .id(uuid::Uuid::new_v4())
-        .name(format!("Test Sender Account {code}"))
-        .code(code)
-        .build()
-        .unwrap();
-    let sender_account_id = ledger.accounts().create(new_account).await.unwrap();
-    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
-    let new_account = NewAccount::builder()
-        .id(uuid::Uuid::new_v4())
-        .name(format!("Test Recipient Account {code}"))
-        .code(code)
-        .build()
-        .unwrap();
-    let recipient_account_id = ledger.accounts().create(new_account).await.unwrap();
+        let tx_code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
 
-    let params = vec![
-        ParamDefinition::builder()
-            .name("recipient")
-            .r#type(ParamDataType::UUID)
-            .build()
-            .unwrap(),
-        ParamDefinition::builder()
-            .name("sender")
-            .r#type(ParamDataType::UUID)
-            .build()
-            .unwrap(),
-        ParamDefinition::builder()
-            .name("journal_id")
-            .r#type(ParamDataType::UUID)
-            .build()
-            .unwrap(),
-        ParamDefinition::builder()
-            .name("external_id")
-            .r#type(ParamDataType::STRING)
-            .build()
-            .unwrap(),
-        ParamDefinition::builder()
-            .name("effective")
-            .r#type(ParamDataType::DATE)
-            .default_expr("date()")
-            .build()
-            .unwrap(),
-    ];
-    let entries = vec![
-        EntryInput::builder()
-            .entry_type("'TEST_BTC_DR'")
-            .account_id("params.sender")
-            .layer("SETTLED")
-            .direction("DEBIT")
-            .units("decimal('1290')")
-            .currency("'BTC'")
-            .build()
-            .unwrap(),
-        EntryInput::builder()
-            .entry_type("'TEST_BTC_CR'")
-            .account_id("params.recipient")
-            .layer("SETTLED")
-            .direction("CREDIT")
-            .units("decimal('1290')")
-            .currency("'BTC'")
-            .build()
-            .unwrap(),
-        EntryInput::builder()
-            .entry_type("'TEST_USD_DR'")
-            .account_id("params.sender")
-            .layer("SETTLED")
-            .direction("DEBIT")
-            .units("decimal('100')")
-            .currency("'USD'")
+        let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let new_journal = NewJournal::builder().name(name).build().unwrap();
+        let ledger = SqlxLedger::new(&pool);
+
+        let journal_id = ledger.journals().create(new_journal).await.unwrap();
+        let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let new_account = NewAccount::builder()
+            .id(uuid::Uuid::new_v4())
+            .name(format!("Test Sender Account {code}"))
+            .code(code)
             .build()
-            .unwrap(),
-        EntryInput::builder()
-            .entry_type("'TEST_USD_CR'")
-            .account_id("params.recipient")
-            .layer("SETTLED")
-            .direction("CREDIT")
-            .units("decimal('100')")
-            .currency("'USD'")
+            .unwrap();
+        let sender_account_id = ledger.accounts().create(new_account).await.unwrap();
+        let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let new_account = NewAccount::builder()
+            .id(uuid::Uuid::new_v4())
+            .name(format!("Test Recipient Account {code}"))
+            .code(code)
             .build()
-            .unwrap(),
-    ];
-    let new_template = NewTxTemplate::builder()
-        .id(uuid::Uuid::new_v4())
-        .code(&tx_code)
-        .params(params)
-        .tx_input(
-            TxInput::builder()
-                .effective("params.effective")
-                .journal_id("params.journal_id")
-                .external_id("params.external_id")
-                .metadata(r#"{"foo": "bar"}"#)
+            .unwrap();
+        let recipient_account_id = ledger.accounts().create(new_account).await.unwrap();
+
+        let params = vec![
+            ParamDefinition::builder()
+                .name("recipient")
+                .r#type(ParamDataType::UUID)
                 .build()
                 .unwrap(),
-        )
-        .entries(entries)
-        .build()
-        .unwrap();
-    ledger.tx_templates().create(new_template).await.unwrap();
+            ParamDefinition::builder()
+                .name("sender")
+                .r#type(ParamDataType::UUID)
+                .build()
+                .unwrap(),
+            ParamDefinition::builder()
+                .name("journal_id")
+                .r#type(ParamDataType::UUID)
+                .build()
+                .unwrap(),
+            ParamDefinition::builder()
+                .name("external_id")
+                .r#type(ParamDataType::STRING)
+                .build()
+                .unwrap(),
+            ParamDefinition::builder()
+                .name("effective")
+                .r#type(ParamDataType::DATE)
+                .default_expr("date()")
+                .build()
+                .unwrap(),
+        ];
+        let entries = vec![
+            EntryInput::builder()
+                .entry_type("'TEST_BTC_DR'")
+                .account_id("params.sender")
+                .layer("SETTLED")
+                .direction("DEBIT")
+                .units("decimal('1290')")
+                .currency("'BTC'")
+                .build()
+                .unwrap(),
+            EntryInput::builder()
+                .entry_type("'TEST_BTC_CR'")
+                .account_id("params.recipient")
+                .layer("SETTLED")
+                .direction("CREDIT")
+                .units("decimal('1290')")
+                .currency("'BTC'")
+                .build()
+                .unwrap(),
+            EntryInput::builder()
+                .entry_type("'TEST_USD_DR'")
+                .account_id("params.sender")
+                .layer("SETTLED")
+                .direction("DEBIT")
+                .units("decimal('100')")
+                .currency("'USD'")
+                .build()
+                .unwrap(),
+            EntryInput::builder()
+                .entry_type("'TEST_USD_CR'")
+                .account_id("params.recipient")
+                .layer("SETTLED")
+                .direction("CREDIT")
+                .units("decimal('100')")
+                .currency("'USD'")
+                .build()
+                .unwrap(),
+        ];
+        let new_template = NewTxTemplate::builder()
+            .id(uuid::Uuid::new_v4())
+            .code(&tx_code)
+            .params(params)
+            .tx_input(
+                TxInput::builder()
+                    .effective("params.effective")
+                    .journal_id("params.journal_id")
+                    .external_id("params.external_id")
+                    .metadata(r#"{"foo": "bar"}"#)
+                    .build()
+                    .unwrap(),
+            )
+            .entries(entries)
+            .build()
+            .unwrap();
+        ledger.tx_templates().create(new_template).await.unwrap();
+
+        Ok(Self {
+            ledger,
+            journal_id,
+            sender_account_id,
+            recipient_account_id,
+            tx_code,
+        })
+    }
+
+    fn params(&self) -> TxParams {
+        let mut params = TxParams::new();
+        params.insert("journal_id", self.journal_id);
+        params.insert("sender", self.sender_account_id);
+        params.insert("recipient", self.recipient_account_id);
+        params.insert("external_id", uuid::Uuid::new_v4().to_string());
+        params
+    }
+
+    async fn post(&self, params: TxParams) -> Result<TransactionId, SqlxLedgerError> {
+        let tx_id = TransactionId::new();
+        self.ledger
+            .post_transaction(tx_id, &self.tx_code, Some(params))
+            .await?;
+        Ok(tx_id)
+    }
+}
+
+#[tokio::test]
+async fn post_transaction() -> anyhow::Result<()> {
+    let Transfer {
+        ledger,
+        journal_id,
+        sender_account_id,
+        recipient_account_id,
+        tx_code,
+    } = Transfer::init().await?;
 
     let events = ledger.events(Default::default()).await?;
     let mut sender_account_balance_events = events
@@ -150,7 +203,7 @@ async fn post_transaction() -> anyhow::Result<()> {
         .list_by_transaction_ids(vec![transactions[0].id])
         .await?;
 
-    assert!(entries.get(&transactions[0].id).is_some());
+    assert!(entries.contains_key(&transactions[0].id));
     assert_eq!(entries.get(&transactions[0].id).unwrap().len(), 4);
 
     assert_eq!(
@@ -270,6 +323,686 @@ async fn post_transaction() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn update_transaction_and_entries() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let ledger = &transfer.ledger;
+    let tx_id = transfer.post(transfer.params()).await?;
+    let tx = ledger.transactions().list_by_ids([tx_id]).await?.remove(0);
+    let mut journal_events = ledger
+        .events(Default::default())
+        .await?
+        .journal(transfer.journal_id)
+        .await
+        .expect("event subscriber closed");
+
+    ledger
+        .transactions()
+        .update(
+            tx.id,
+            Some("corrected".to_string()),
+            Some(serde_json::json!({ "foo": "baz" })),
+        )
+        .await?;
+    ledger
+        .transactions()
+        .update::<()>(tx.id, None, None)
+        .await?;
+    let stale = ledger
+        .transactions()
+        .update_with_version::<()>(tx.id, tx.version, Some("stale".to_string()), None)
+        .await;
+    assert!(matches!(
+        stale,
+        Err(SqlxLedgerError::OptimisticLockingError)
+    ));
+    let updated = ledger.transactions().list_by_ids([tx.id]).await?;
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].version, 3);
+    assert_eq!(updated[0].description.as_deref(), Some("corrected"));
+    assert_eq!(
+        updated[0].metadata_json,
+        Some(serde_json::json!({ "foo": "baz" }))
+    );
+    assert_eq!(updated[0].effective, tx.effective);
+    loop {
+        let event = journal_events.recv().await.unwrap();
+        if let SqlxLedgerEventData::TransactionUpdated(updated) = event.data {
+            assert_eq!(updated.id, tx.id);
+            break;
+        }
+    }
+
+    let entries = ledger.entries().list_by_transaction_ids([tx.id]).await?;
+    let entry = &entries.get(&tx.id).unwrap()[0];
+    ledger
+        .entries()
+        .update_description_with_version(entry.id, entry.version, "corrected".to_string())
+        .await?;
+    let updated = ledger.entries().list_by_transaction_ids([tx.id]).await?;
+    let updated = &updated.get(&tx.id).unwrap();
+    assert_eq!(updated.len(), 4);
+    assert_eq!(updated[0].version, 2);
+    assert_eq!(updated[0].description.as_deref(), Some("corrected"));
+    assert_eq!(updated[0].units, entry.units);
+
+    let missing = TransactionId::new();
+    assert!(matches!(
+        ledger.transactions().update::<()>(missing, None, None).await,
+        Err(SqlxLedgerError::TransactionNotFound(id)) if id == missing
+    ));
+    let missing = EntryId::new();
+    assert!(matches!(
+        ledger
+            .entries()
+            .update_description(missing, "corrected".to_string())
+            .await,
+        Err(SqlxLedgerError::EntryNotFound(id)) if id == missing
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_transactions() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let ledger = &transfer.ledger;
+    let first_id = transfer.post(transfer.params()).await?;
+    let external_id = uuid::Uuid::new_v4().to_string();
+    let mut params = transfer.params();
+    params.insert("external_id", external_id.clone());
+    transfer.post(params).await?;
+    let tx = ledger
+        .transactions()
+        .list_by_ids([first_id])
+        .await?
+        .remove(0);
+    ledger
+        .transactions()
+        .update(
+            tx.id,
+            Some("corrected".to_string()),
+            Some(serde_json::json!({ "foo": "baz" })),
+        )
+        .await?;
+
+    let filter = TransactionFilter {
+        journal_id: Some(transfer.journal_id),
+        tx_template_code: Some(transfer.tx_code.clone()),
+        ..Default::default()
+    };
+    let first = ledger
+        .transactions()
+        .list(
+            filter.clone(),
+            PaginatedQueryArgs {
+                first: 1,
+                after: None,
+            },
+        )
+        .await?;
+    assert_eq!(first.entities.len(), 1);
+    assert!(first.has_next_page);
+    assert_eq!(first.entities[0].id, tx.id);
+    assert_eq!(first.entities[0].version, 2);
+    let second = ledger
+        .transactions()
+        .list(
+            filter.clone(),
+            PaginatedQueryArgs {
+                first: 1,
+                after: first.end_cursor,
+            },
+        )
+        .await?;
+    assert_eq!(second.entities.len(), 1);
+    assert!(!second.has_next_page);
+    assert_eq!(second.entities[0].external_id, external_id);
+
+    let page = ledger
+        .transactions()
+        .list(
+            TransactionFilter {
+                metadata: Some(serde_json::json!({ "foo": "baz" })),
+                correlation_id: Some(tx.correlation_id),
+                effective_from: Some(tx.effective),
+                effective_to: Some(tx.effective),
+                ..filter.clone()
+            },
+            PaginatedQueryArgs::default(),
+        )
+        .await?;
+    assert_eq!(page.entities.len(), 1);
+    assert_eq!(page.entities[0].id, tx.id);
+    let page = ledger
+        .transactions()
+        .list(
+            TransactionFilter {
+                created_until: Some(tx.created_at),
+                ..filter
+            },
+            PaginatedQueryArgs::default(),
+        )
+        .await?;
+    assert!(page.entities.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn post_to_closed_period() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let ledger = &transfer.ledger;
+    let tx_id = transfer.post(transfer.params()).await?;
+    let tx = ledger.transactions().list_by_ids([tx_id]).await?.remove(0);
+
+    ledger
+        .journals()
+        .close_period(transfer.journal_id, tx.effective)
+        .await?;
+    let late_params = || {
+        let mut params = transfer.params();
+        params.insert("effective", tx.effective);
+        params
+    };
+    let result = transfer.post(late_params()).await;
+    assert!(matches!(
+        result,
+        Err(SqlxLedgerError::PeriodClosed(_, date)) if date == tx.effective
+    ));
+    let reopened = ledger
+        .journals()
+        .reopen_period(transfer.journal_id, tx.effective, "late adjustment")
+        .await?;
+    assert_eq!(reopened.closed_through, tx.effective.pred_opt());
+    transfer.post(late_params()).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn posting_and_closing_periods_lock_the_journal() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let pg_host = std::env::var("PG_HOST").unwrap_or("localhost".to_string());
+    let pool = sqlx::PgPool::connect(&format!(
+        "postgres://user:password@{pg_host}:5432/pg?options=-c%20lock_timeout%3D200"
+    ))
+    .await?;
+    let ledger = SqlxLedger::new(&pool);
+    let is_lock_timeout = |e: Option<SqlxLedgerError>| match e {
+        Some(SqlxLedgerError::Sqlx(sqlx::Error::Database(e))) => {
+            e.code().as_deref() == Some("55P03")
+        }
+        _ => false,
+    };
+
+    // A post in flight keeps the periods from being closed
+    let mut in_flight = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock_shared(hashtextextended($1::UUID::TEXT, 0))")
+        .bind(uuid::Uuid::from(transfer.journal_id))
+        .execute(&mut *in_flight)
+        .await?;
+    let effective = chrono::Utc::now().date_naive();
+    let result = ledger
+        .journals()
+        .close_period(transfer.journal_id, effective)
+        .await;
+    assert!(is_lock_timeout(result.err()));
+    in_flight.commit().await?;
+
+    // A closing in flight keeps posts waiting until it is visible
+    let mut in_flight = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::UUID::TEXT, 0))")
+        .bind(uuid::Uuid::from(transfer.journal_id))
+        .execute(&mut *in_flight)
+        .await?;
+    let result = ledger
+        .post_transaction(
+            TransactionId::new(),
+            &transfer.tx_code,
+            Some(transfer.params()),
+        )
+        .await;
+    assert!(is_lock_timeout(result.err()));
+    in_flight.commit().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn post_to_locked_journal() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let ledger = &transfer.ledger;
+    ledger
+        .journals()
+        .update_status(transfer.journal_id, JournalStatus::Locked)
+        .await?;
+    let result = transfer.post(transfer.params()).await;
+    assert!(matches!(
+        result,
+        Err(SqlxLedgerError::JournalNotActive(id, JournalStatus::Locked)) if id == transfer.journal_id
+    ));
+
+    let mut params = transfer.params();
+    let missing_journal_id = JournalId::new();
+    params.insert("journal_id", missing_journal_id);
+    let result = transfer.post(params).await;
+    assert!(matches!(
+        result,
+        Err(SqlxLedgerError::JournalNotFound(id)) if id == missing_journal_id
+    ));
+
+    Ok(())
+}
+
+/// Creates a template posting `entries` (layer, direction, account param) of
+/// 100 USD each under the correlation id passed in `params.correlation_id`.
+async fn create_correlated_template(
+    ledger: &SqlxLedger,
+    entries: &[(&str, &str, &str)],
+) -> anyhow::Result<String> {
+    let tx_code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let params = ["journal_id", "correlation_id", "sender", "recipient"]
+        .into_iter()
+        .map(|name| {
+            ParamDefinition::builder()
+                .name(name)
+                .r#type(ParamDataType::UUID)
+                .build()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let entries = entries
+        .iter()
+        .map(|(layer, direction, account)| {
+            EntryInput::builder()
+                .entry_type(format!("'TEST_{layer}_{direction}'"))
+                .account_id(format!("params.{account}"))
+                .layer(*layer)
+                .direction(*direction)
+                .units("decimal('100')")
+                .currency("'USD'")
+                .build()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let new_template = NewTxTemplate::builder()
+        .id(uuid::Uuid::new_v4())
+        .code(&tx_code)
+        .params(params)
+        .tx_input(
+            TxInput::builder()
+                .effective("date()")
+                .journal_id("params.journal_id")
+                .correlation_id("params.correlation_id")
+                .build()
+                .unwrap(),
+        )
+        .entries(entries)
+        .build()
+        .unwrap();
+    ledger.tx_templates().create(new_template).await?;
+    Ok(tx_code)
+}
+
+#[tokio::test]
+async fn correlation_summary() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let ledger = &transfer.ledger;
+    let hold_code = create_correlated_template(
+        ledger,
+        &[
+            ("PENDING", "DEBIT", "sender"),
+            ("PENDING", "CREDIT", "recipient"),
+        ],
+    )
+    .await?;
+    let settle_code = create_correlated_template(
+        ledger,
+        &[
+            ("PENDING", "CREDIT", "sender"),
+            ("PENDING", "DEBIT", "recipient"),
+            ("SETTLED", "DEBIT", "sender"),
+            ("SETTLED", "CREDIT", "recipient"),
+        ],
+    )
+    .await?;
+
+    let correlation_id = uuid::Uuid::new_v4();
+    let params = || {
+        let mut params = TxParams::new();
+        params.insert("journal_id", transfer.journal_id);
+        params.insert("correlation_id", correlation_id);
+        params.insert("sender", transfer.sender_account_id);
+        params.insert("recipient", transfer.recipient_account_id);
+        params
+    };
+    let hold_id = TransactionId::new();
+    ledger
+        .post_transaction(hold_id, &hold_code, Some(params()))
+        .await?;
+    transfer.post(transfer.params()).await?;
+    let settle_id = TransactionId::new();
+    ledger
+        .post_transaction(settle_id, &settle_code, Some(params()))
+        .await?;
+
+    let summary = ledger
+        .correlation_summary(CorrelationId::from(correlation_id))
+        .await?;
+    assert_eq!(
+        summary
+            .transactions
+            .iter()
+            .map(|tx| tx.id)
+            .collect::<Vec<_>>(),
+        vec![hold_id, settle_id]
+    );
+    assert_eq!(summary.entries.get(&hold_id).unwrap().len(), 2);
+    assert_eq!(summary.entries.get(&settle_id).unwrap().len(), 4);
+    assert_eq!(summary.net_effects.len(), 4);
+    let effect = |account_id, layer| {
+        summary
+            .net_effects
+            .iter()
+            .find(|e| {
+                e.account_id == account_id
+                    && e.layer == layer
+                    && e.currency == "USD".parse().unwrap()
+            })
+            .unwrap()
+    };
+    let sender_pending = effect(transfer.sender_account_id, Layer::Pending);
+    assert_eq!(sender_pending.dr_amount, Decimal::from(100));
+    assert_eq!(sender_pending.cr_amount, Decimal::from(100));
+    assert_eq!(sender_pending.net(DebitOrCredit::Debit), Decimal::ZERO);
+    let recipient_pending = effect(transfer.recipient_account_id, Layer::Pending);
+    assert_eq!(recipient_pending.net(DebitOrCredit::Credit), Decimal::ZERO);
+    let sender_settled = effect(transfer.sender_account_id, Layer::Settled);
+    assert_eq!(sender_settled.dr_amount, Decimal::from(100));
+    assert_eq!(sender_settled.cr_amount, Decimal::ZERO);
+    assert_eq!(
+        sender_settled.net(DebitOrCredit::Credit),
+        Decimal::from(-100)
+    );
+    let recipient_settled = effect(transfer.recipient_account_id, Layer::Settled);
+    assert_eq!(
+        recipient_settled.net(DebitOrCredit::Credit),
+        Decimal::from(100)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn post_transaction_with_custom_functions() -> anyhow::Result<()> {
+    let pool = helpers::init_pool().await?;
+
+    let tx_code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_journal = NewJournal::builder().name(name).build().unwrap();
+
+    let unused_loads = Arc::new(AtomicUsize::new(0));
+    let loads = Arc::clone(&unused_loads);
+    let mut functions = FunctionRegistry::new();
+    functions
+        .add_function("fee_schedule", |args| match args.first() {
+            Some(CelValue::String(tier)) if tier.as_str() == "gold" => {
+                Ok(CelValue::Decimal(Decimal::from(5)))
+            }
+            Some(CelValue::String(tier)) if tier.as_str() == "blocked" => {
+                Err(CelError::Unexpected("tier is blocked".to_string()))
+            }
+            _ => Ok(CelValue::Decimal(Decimal::from(10))),
+        })
+        .add_async_function("fx_rate", || async {
+            // Stands in for a rate fetched from a remote service
+            let rate = Decimal::from(2);
+            Ok(move |_: Vec<CelValue>| -> Result<CelValue, CelError> {
+                Ok(CelValue::Decimal(rate))
+            })
+        })
+        .add_async_function("unused_rate", move || {
+            loads.fetch_add(1, Ordering::SeqCst);
+            async { Ok(|_: Vec<CelValue>| -> Result<CelValue, CelError> { Ok(CelValue::Null) }) }
+        })
+        .add_message_type(
+            "Metadata",
+            [("tier", CelType::String), ("fee", CelType::Decimal)],
+        );
+    let clock = CelClock::fixed("2024-03-01T03:30:00Z".parse()?)
+        .with_timezone("America/El_Salvador".parse::<Tz>().unwrap());
+    let ledger = SqlxLedger::new(&pool)
+        .with_function_registry(functions.clone())
+        .with_clock(clock);
+
+    let journal_id = ledger.journals().create(new_journal).await.unwrap();
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_account = NewAccount::builder()
+        .id(uuid::Uuid::new_v4())
+        .name(format!("Test Sender Account {code}"))
+        .code(code)
+        .build()
+        .unwrap();
+    let sender_account_id = ledger.accounts().create(new_account).await.unwrap();
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let new_account = NewAccount::builder()
+        .id(uuid::Uuid::new_v4())
+        .name(format!("Test Recipient Account {code}"))
+        .code(code)
+        .build()
+        .unwrap();
+    let recipient_account_id = ledger.accounts().create(new_account).await.unwrap();
+
+    let params = vec![
+        ParamDefinition::builder()
+            .name("recipient")
+            .r#type(ParamDataType::UUID)
+            .build()
+            .unwrap(),
+        ParamDefinition::builder()
+            .name("sender")
+            .r#type(ParamDataType::UUID)
+            .build()
+            .unwrap(),
+        ParamDefinition::builder()
+            .name("journal_id")
+            .r#type(ParamDataType::UUID)
+            .build()
+            .unwrap(),
+        ParamDefinition::builder()
+            .name("tier")
+            .r#type(ParamDataType::STRING)
+            .build()
+            .unwrap(),
+    ];
+    let entries = vec![
+        EntryInput::builder()
+            .entry_type("'TEST_FEE_DR'")
+            .account_id("params.sender")
+            .layer("SETTLED")
+            .direction("DEBIT")
+            .units("fee_schedule(params.tier) * fx_rate('USD', 'EUR')")
+            .currency("'EUR'")
+            .build()
+            .unwrap(),
+        EntryInput::builder()
+            .entry_type("'TEST_FEE_CR'")
+            .account_id("params.recipient")
+            .layer("SETTLED")
+            .direction("CREDIT")
+            .units("fee_schedule(params.tier) * fx_rate('USD', 'EUR')")
+            .currency("'EUR'")
+            .build()
+            .unwrap(),
+    ];
+    let new_template = NewTxTemplate::builder()
+        .id(uuid::Uuid::new_v4())
+        .code(&tx_code)
+        .params(params)
+        .tx_input(
+            TxInput::builder()
+                .effective("date()")
+                .journal_id("params.journal_id")
+                .metadata("Metadata{tier: params.tier, fee: 5.00m}")
+                .build()
+                .unwrap(),
+        )
+        .entries(entries)
+        .function_registry(&functions)
+        .build()
+        .unwrap();
+    ledger.tx_templates().create(new_template).await.unwrap();
+
+    let mut params = TxParams::new();
+    params.insert("journal_id", journal_id);
+    params.insert("sender", sender_account_id);
+    params.insert("recipient", recipient_account_id);
+    params.insert("tier", "gold");
+    let tx_id = TransactionId::new();
+    ledger
+        .post_transaction(tx_id, &tx_code, Some(params))
+        .await
+        .unwrap();
+    let transactions = ledger.transactions().list_by_ids([tx_id]).await?;
+    assert_eq!(
+        transactions[0].metadata_json,
+        Some(serde_json::json!({"tier": "gold", "fee": "5.00"}))
+    );
+    assert_eq!(
+        transactions[0].effective,
+        chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+    );
+
+    let eur = rusty_money::iso::find("EUR").unwrap();
+    let balance = get_balance(
+        &ledger,
+        journal_id,
+        recipient_account_id,
+        Currency::Iso(eur),
+    )
+    .await?;
+    assert_eq!(balance.settled(), Decimal::from(10));
+    assert_eq!(unused_loads.load(Ordering::SeqCst), 0);
+
+    let mut params = TxParams::new();
+    params.insert("journal_id", journal_id);
+    params.insert("sender", sender_account_id);
+    params.insert("recipient", recipient_account_id);
+    params.insert("tier", "blocked");
+    let err = ledger
+        .post_transaction(TransactionId::new(), &tx_code, Some(params))
+        .await
+        .unwrap_err();
+    match err {
+        SqlxLedgerError::TxTemplateEvaluation(code, field, e) => {
+            assert_eq!(code, tx_code);
+            assert_eq!(field, "entries[0].units");
+            match *e {
+                SqlxLedgerError::CelError(CelError::EvaluationError(location, _)) => {
+                    assert_eq!(location.snippet, "fee_schedule(params.tier)");
+                }
+                e => panic!("unexpected error {e}"),
+            }
+        }
+        e => panic!("unexpected error {e}"),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn load_async_function_called_from_param_default() -> anyhow::Result<()> {
+    let transfer = Transfer::init().await?;
+    let mut functions = FunctionRegistry::new();
+    functions
+        .add_async_function("base_fee", || async {
+            // Stands in for a fee fetched from a remote service
+            Ok(|_: Vec<CelValue>| -> Result<CelValue, CelError> {
+                Ok(CelValue::Decimal(Decimal::from(7)))
+            })
+        })
+        .return_type("base_fee", CelType::Decimal);
+    let ledger = transfer
+        .ledger
+        .clone()
+        .with_function_registry(functions.clone());
+
+    let tx_code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let params = vec![
+        ParamDefinition::builder()
+            .name("journal_id")
+            .r#type(ParamDataType::UUID)
+            .build()
+            .unwrap(),
+        ParamDefinition::builder()
+            .name("sender")
+            .r#type(ParamDataType::UUID)
+            .build()
+            .unwrap(),
+        ParamDefinition::builder()
+            .name("recipient")
+            .r#type(ParamDataType::UUID)
+            .build()
+            .unwrap(),
+        ParamDefinition::builder()
+            .name("fee")
+            .r#type(ParamDataType::DECIMAL)
+            .default_expr("base_fee()")
+            .build()
+            .unwrap(),
+    ];
+    let entry = |account: &str, direction: &str| {
+        EntryInput::builder()
+            .entry_type("'TEST_FEE'")
+            .account_id(account)
+            .layer("SETTLED")
+            .direction(direction)
+            .units("params.fee")
+            .currency("'USD'")
+            .build()
+            .unwrap()
+    };
+    let new_template = NewTxTemplate::builder()
+        .id(uuid::Uuid::new_v4())
+        .code(&tx_code)
+        .params(params)
+        .tx_input(
+            TxInput::builder()
+                .effective("date()")
+                .journal_id("params.journal_id")
+                .build()
+                .unwrap(),
+        )
+        .entries(vec![
+            entry("params.sender", "DEBIT"),
+            entry("params.recipient", "CREDIT"),
+        ])
+        .function_registry(&functions)
+        .build()
+        .unwrap();
+    ledger.tx_templates().create(new_template).await?;
+
+    let mut params = TxParams::new();
+    params.insert("journal_id", transfer.journal_id);
+    params.insert("sender", transfer.sender_account_id);
+    params.insert("recipient", transfer.recipient_account_id);
+    ledger
+        .post_transaction(TransactionId::new(), &tx_code, Some(params))
+        .await?;
+    let usd = rusty_money::iso::find("USD").unwrap();
+    let balance = get_balance(
+        &ledger,
+        transfer.journal_id,
+        transfer.recipient_account_id,
+        Currency::Iso(usd),
+    )
+    .await?;
+    assert_eq!(balance.settled(), Decimal::from(7));
+
+    Ok(())
+}
+
 async fn get_balance(
     ledger: &SqlxLedger,
     journal_id: JournalId,
@@ -16,16 +16,17 @@ async fn test_tx_template() -> anyhow::Result<()> {
         .build()
         .unwrap()];
     let tx_input = TxInput::builder()
-        .effective("1")
-        .journal_id("1")
+        .effective("date()")
+        .journal_id(format!("uuid('{}')", uuid::Uuid::new_v4()))
+        .description("params.input1")
         .build()
         .unwrap();
     let entries = vec![EntryInput::builder()
         .entry_type("'TEST_DR'")
-        .account_id("param.recipient")
-        .layer("'Settled'")
-        .direction("'Settled'")
-        .units("1290")
+        .account_id(format!("uuid('{}')", uuid::Uuid::new_v4()))
+        .layer("SETTLED")
+        .direction("DEBIT")
+        .units("decimal('1290')")
         .currency("'BTC'")
         .build()
         .unwrap()];
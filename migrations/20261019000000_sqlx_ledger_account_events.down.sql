@@ -0,0 +1,2 @@
+DROP TRIGGER sqlx_ledger_accounts ON sqlx_ledger_accounts;
+DROP FUNCTION sqlx_ledger_accounts_event();
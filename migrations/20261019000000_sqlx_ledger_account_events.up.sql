@@ -0,0 +1,14 @@
+CREATE FUNCTION sqlx_ledger_accounts_event() RETURNS TRIGGER AS $$
+BEGIN
+  INSERT INTO sqlx_ledger_events (type, data, recorded_at)
+  SELECT CASE
+           WHEN NEW.version > 1 THEN 'AccountUpdated'
+           ELSE 'AccountCreated'
+         END as type,
+        row_to_json(NEW),
+        NEW.modified_at;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER sqlx_ledger_accounts AFTER INSERT ON sqlx_ledger_accounts
+  FOR EACH ROW EXECUTE FUNCTION sqlx_ledger_accounts_event();
@@ -0,0 +1,5 @@
+DROP INDEX sqlx_ledger_transactions_created_at_idx;
+DROP INDEX sqlx_ledger_transactions_journal_id_effective_idx;
+DROP INDEX sqlx_ledger_transactions_correlation_id_idx;
+DROP INDEX sqlx_ledger_transactions_tx_template_id_idx;
+DROP INDEX sqlx_ledger_transactions_metadata_idx;
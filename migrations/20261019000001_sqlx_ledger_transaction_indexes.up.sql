@@ -0,0 +1,5 @@
+CREATE INDEX sqlx_ledger_transactions_created_at_idx ON sqlx_ledger_transactions (created_at, id);
+CREATE INDEX sqlx_ledger_transactions_journal_id_effective_idx ON sqlx_ledger_transactions (journal_id, effective);
+CREATE INDEX sqlx_ledger_transactions_correlation_id_idx ON sqlx_ledger_transactions (correlation_id);
+CREATE INDEX sqlx_ledger_transactions_tx_template_id_idx ON sqlx_ledger_transactions (tx_template_id);
+CREATE INDEX sqlx_ledger_transactions_metadata_idx ON sqlx_ledger_transactions USING GIN (metadata jsonb_path_ops);
@@ -0,0 +1,11 @@
+-- Refuse to roll back while any journal uses the new statuses rather than
+-- silently reactivating locked or archived journals.
+DO $$
+BEGIN
+  IF EXISTS (SELECT 1 FROM sqlx_ledger_journals WHERE status <> 'active') THEN
+    RAISE EXCEPTION 'journals with status locked or archived exist, reactivate them before rolling back';
+  END IF;
+END $$;
+
+ALTER TABLE sqlx_ledger_journals ALTER COLUMN status TYPE Status USING status::TEXT::Status;
+DROP TYPE JournalStatus;
@@ -0,0 +1,2 @@
+CREATE TYPE JournalStatus AS ENUM ('active', 'locked', 'archived');
+ALTER TABLE sqlx_ledger_journals ALTER COLUMN status TYPE JournalStatus USING status::TEXT::JournalStatus;
@@ -0,0 +1,8 @@
+CREATE TABLE sqlx_ledger_periods (
+  journal_id UUID NOT NULL,
+  version INT NOT NULL,
+  closed_through DATE,
+  reason VARCHAR,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  UNIQUE(journal_id, version)
+);
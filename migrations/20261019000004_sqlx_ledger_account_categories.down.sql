@@ -0,0 +1,2 @@
+ALTER TABLE sqlx_ledger_accounts DROP COLUMN category;
+DROP TYPE AccountCategory;
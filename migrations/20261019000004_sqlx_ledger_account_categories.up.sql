@@ -0,0 +1,22 @@
+CREATE TYPE AccountCategory AS ENUM (
+  'asset',
+  'contra_asset',
+  'liability',
+  'contra_liability',
+  'equity',
+  'contra_equity',
+  'revenue',
+  'contra_revenue',
+  'expense',
+  'contra_expense'
+);
+
+ALTER TABLE sqlx_ledger_accounts ADD COLUMN category AccountCategory;
+
+-- Mirrors AccountCategory::normal_balance_type
+ALTER TABLE sqlx_ledger_accounts ADD CONSTRAINT sqlx_ledger_accounts_category_normal_balance_type CHECK (
+  category IS NULL OR normal_balance_type = CASE
+    WHEN category IN ('asset', 'expense', 'contra_liability', 'contra_equity', 'contra_revenue') THEN 'debit'::DebitOrCredit
+    ELSE 'credit'::DebitOrCredit
+  END
+);